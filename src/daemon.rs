@@ -12,8 +12,8 @@ use std::{
 	time::Duration,
 };
 
-use anyhow::{Context, Result};
-use log::{debug, error, warn};
+use anyhow::{Context, Result, bail};
+use log::{debug, error, info, warn};
 use serde::Deserialize;
 
 use crate::{
@@ -21,6 +21,12 @@ use crate::{
 	ppd::{PowerProfilesDaemon, PpdProfile},
 	sensors::{
 		SensorConfig, SensorInfo,
+		cooling_device::cooling_devices_restore,
+		dptf_gddv::dptf_dump,
+		fan::fans_restore_and_exit,
+		hwmon::SensorReadings,
+		intel_dptf::dptf_add_hids,
+		power_monitor::power_monitor,
 		throttle::{cpu_throttling, graphics_throttling, ring_throttling},
 	},
 	upower::UPowerConnection,
@@ -45,6 +51,9 @@ pub struct DaemonConfig {
 	pub default: Option<DefaultProfiles>,
 	pub ppd: PowerProfilesDaemonProfiles,
 	pub poll_frequency: Option<u64>,
+	/// Additional ACPI HIDs of the DPTF (int3400) device
+	#[serde(default)]
+	pub dptf_hids: Vec<String>,
 }
 
 #[derive(Eq, PartialEq, Clone)]
@@ -58,6 +67,8 @@ pub struct CurrentProfile {
 	pub manual: Option<ProfileInfo>,
 	pub ppd_profile: PpdProfile,
 	pub ppd_set: bool,
+	/// Path of the profile that was last written successfully, to tell transitions from reapplies
+	pub last_applied: Option<PathBuf>,
 }
 impl CurrentProfile {
 	pub fn get_override(&self) -> Option<&ProfileInfo> {
@@ -77,27 +88,59 @@ fn read_cfg(profiles: &Path, path: &Path) -> Result<ProfileInfo> {
 	Ok(ProfileInfo { cfg, path })
 }
 
-pub fn apply_cfg_from_file(profiles: &Path, path: &Path) -> Result<ProfileInfo> {
+/// `transition` is false when reapplying the profile that was applied last, see
+/// [`SensorConfig::apply`]
+pub fn apply_cfg_from_file(profiles: &Path, path: &Path, transition: bool) -> Result<ProfileInfo> {
 	let info = read_cfg(profiles, path)?;
 
-	apply_cfg(&info.cfg)?;
+	info.cfg.write(transition)?;
 
 	Ok(info)
 }
 
-fn apply_cfg(cfg: &SensorConfig) -> Result<()> {
-	let mut info = SensorInfo::read().context("failed to read current sensor data")?;
-	cfg.apply(&mut info).context("failed to apply config")?;
-	info.write().context("failed to write config")?;
+/// Blocks SIGINT and SIGTERM in every thread spawned afterwards and hands fans and cooling devices
+/// back to the firmware when one arrives
+fn spawn_signal_handler() -> Result<()> {
+	// SAFETY: the set is initialized by sigemptyset before use
+	let mut set: libc::sigset_t = unsafe { std::mem::zeroed() };
+	// SAFETY: set is a valid sigset_t
+	let ret = unsafe {
+		libc::sigemptyset(&mut set);
+		libc::sigaddset(&mut set, libc::SIGINT);
+		libc::sigaddset(&mut set, libc::SIGTERM);
+		libc::pthread_sigmask(libc::SIG_BLOCK, &set, std::ptr::null_mut())
+	};
+	if ret != 0 {
+		bail!(
+			"failed to block signals: {}",
+			std::io::Error::from_raw_os_error(ret)
+		);
+	}
+
+	std::thread::spawn(move || {
+		let mut signal = 0;
+		// SAFETY: set and signal are valid for the duration of the call
+		if unsafe { libc::sigwait(&set, &mut signal) } == 0 {
+			info!("received signal {signal}, exiting");
+			cooling_devices_restore();
+			fans_restore_and_exit(0);
+		}
+	});
+
 	Ok(())
 }
 
 pub fn daemon(cfg: DaemonConfig) -> Result<()> {
+	dptf_add_hids(&cfg.dptf_hids);
+	// has to happen before any other thread is spawned so they inherit the signal mask
+	spawn_signal_handler().context("failed to set up signal handling")?;
+
 	let current: CurrentState = Arc::new(Mutex::new(CurrentProfile {
 		held: None,
 		manual: None,
 		ppd_profile: PpdProfile::Balanced,
 		ppd_set: false,
+		last_applied: None,
 	}));
 
 	let (tx, rx) = channel::<()>();
@@ -128,10 +171,14 @@ pub fn daemon(cfg: DaemonConfig) -> Result<()> {
 				let mut current = current.lock().unwrap();
 
 				let ppd_profile = if let Some(cfg) = current.get_override() {
-					if let Err(err) = apply_cfg(&cfg.cfg) {
-						warn!("failed to restore cfg: {err:?}");
+					let transition = current.last_applied.as_ref() != Some(&cfg.path);
+					let path = cfg.path.clone();
+					let ppd_name = cfg.cfg.ppd_name;
+					match cfg.cfg.write(transition) {
+						Ok(()) => current.last_applied = Some(path),
+						Err(err) => warn!("failed to restore cfg: {err:?}"),
 					}
-					Some(cfg.cfg.ppd_name)
+					Some(ppd_name)
 				} else if let Some(default) = &cfg.default {
 					match upower.query_on_battery() {
 						Ok(on_battery) => {
@@ -141,8 +188,13 @@ pub fn daemon(cfg: DaemonConfig) -> Result<()> {
 								&default.ac
 							};
 
-							match apply_cfg_from_file(&cfg.profiles, path) {
-								Ok(info) => Some(info.cfg.ppd_name),
+							let transition = current.last_applied != Some(cfg.profiles.join(path));
+
+							match apply_cfg_from_file(&cfg.profiles, path, transition) {
+								Ok(info) => {
+									current.last_applied = Some(info.path);
+									Some(info.cfg.ppd_name)
+								}
 								Err(err) => {
 									warn!("failed to apply default config: {err:?}");
 									None
@@ -255,6 +307,7 @@ fn handle(
 			}
 
 			writeln!(socket, "\n{}", SensorInfo::read()?)?;
+			writeln!(socket, "{}", SensorReadings::read()?)?;
 		}
 		Action::Dump => {
 			writeln!(
@@ -264,10 +317,11 @@ fn handle(
 			)?;
 		}
 		Action::Apply { path } => {
-			let info = apply_cfg_from_file(profiles, &path)?;
+			let info = apply_cfg_from_file(profiles, &path, true)?;
 			let mut current = current.lock().unwrap();
 			current.ppd_profile = info.cfg.ppd_name;
 			current.ppd_set = false;
+			current.last_applied = Some(info.path.clone());
 			current.manual.replace(info);
 			current.held.take();
 			drop(current);
@@ -283,6 +337,12 @@ fn handle(
 			drop(current);
 			let _ = tx.send(());
 		}
+		Action::DptfDump => {
+			writeln!(socket, "{}", dptf_dump()?)?;
+		}
+		Action::Monitor { interval } => {
+			power_monitor(&mut socket, Duration::from_secs(interval))?;
+		}
 		Action::ThrottleInfo { targets } => {
 			for target in targets {
 				writeln!(
@@ -0,0 +1,353 @@
+//! Minimal decoder for the legacy `.lzma` ("LZMA alone") format, used by compressed DPTF data
+//! vaults. Follows the reference decoder in the LZMA SDK's `LzmaSpec.cpp`.
+
+use anyhow::{Context, Result, bail};
+
+const PROB_INIT: u16 = 1 << 10;
+const NUM_STATES: usize = 12;
+const POS_STATES_MAX: usize = 1 << 4;
+const END_POS_MODEL_INDEX: u32 = 14;
+const NUM_FULL_DISTANCES: usize = 1 << (END_POS_MODEL_INDEX >> 1);
+const NUM_ALIGN_BITS: usize = 4;
+const MATCH_MIN_LEN: usize = 2;
+
+struct RangeDecoder<'a> {
+	buf: &'a [u8],
+	offset: usize,
+	range: u32,
+	code: u32,
+}
+impl<'a> RangeDecoder<'a> {
+	fn new(buf: &'a [u8]) -> Result<Self> {
+		if buf.first() != Some(&0) {
+			bail!("invalid lzma range coder header");
+		}
+		let mut rc = Self {
+			buf,
+			offset: 1,
+			range: u32::MAX,
+			code: 0,
+		};
+		for _ in 0..4 {
+			rc.code = (rc.code << 8) | rc.next_byte()?;
+		}
+		if rc.code == rc.range {
+			bail!("corrupted lzma stream");
+		}
+
+		Ok(rc)
+	}
+
+	fn next_byte(&mut self) -> Result<u32> {
+		let byte = *self.buf.get(self.offset).context("lzma stream truncated")?;
+		self.offset += 1;
+		Ok(byte.into())
+	}
+
+	fn normalize(&mut self) -> Result<()> {
+		if self.range < 1 << 24 {
+			self.range <<= 8;
+			self.code = (self.code << 8) | self.next_byte()?;
+		}
+		Ok(())
+	}
+
+	fn direct_bits(&mut self, count: u32) -> Result<u32> {
+		let mut res = 0;
+		for _ in 0..count {
+			self.range >>= 1;
+			self.code = self.code.wrapping_sub(self.range);
+			let t = 0u32.wrapping_sub(self.code >> 31);
+			self.code = self.code.wrapping_add(self.range & t);
+			if self.code == self.range {
+				bail!("corrupted lzma stream");
+			}
+			self.normalize()?;
+			res = (res << 1) + t.wrapping_add(1);
+		}
+		Ok(res)
+	}
+
+	fn bit(&mut self, prob: &mut u16) -> Result<u32> {
+		let bound = (self.range >> 11) * u32::from(*prob);
+		let bit = if self.code < bound {
+			*prob += ((1 << 11) - *prob) >> 5;
+			self.range = bound;
+			0
+		} else {
+			*prob -= *prob >> 5;
+			self.code -= bound;
+			self.range -= bound;
+			1
+		};
+		self.normalize()?;
+		Ok(bit)
+	}
+
+	fn bit_tree(&mut self, probs: &mut [u16], bits: u32) -> Result<u32> {
+		let mut m = 1;
+		for _ in 0..bits {
+			m = (m << 1) + self.bit(&mut probs[m as usize])?;
+		}
+		Ok(m - (1 << bits))
+	}
+
+	fn bit_tree_reverse(&mut self, probs: &mut [u16], bits: u32) -> Result<u32> {
+		let mut m = 1;
+		let mut symbol = 0;
+		for i in 0..bits {
+			let bit = self.bit(&mut probs[m as usize])?;
+			m = (m << 1) + bit;
+			symbol |= bit << i;
+		}
+		Ok(symbol)
+	}
+}
+
+struct LenDecoder {
+	choice: u16,
+	choice2: u16,
+	low: [[u16; 1 << 3]; POS_STATES_MAX],
+	mid: [[u16; 1 << 3]; POS_STATES_MAX],
+	high: [u16; 1 << 8],
+}
+impl LenDecoder {
+	fn new() -> Self {
+		Self {
+			choice: PROB_INIT,
+			choice2: PROB_INIT,
+			low: [[PROB_INIT; 1 << 3]; POS_STATES_MAX],
+			mid: [[PROB_INIT; 1 << 3]; POS_STATES_MAX],
+			high: [PROB_INIT; 1 << 8],
+		}
+	}
+
+	fn decode(&mut self, rc: &mut RangeDecoder, pos_state: usize) -> Result<usize> {
+		Ok(if rc.bit(&mut self.choice)? == 0 {
+			rc.bit_tree(&mut self.low[pos_state], 3)? as usize
+		} else if rc.bit(&mut self.choice2)? == 0 {
+			8 + rc.bit_tree(&mut self.mid[pos_state], 3)? as usize
+		} else {
+			16 + rc.bit_tree(&mut self.high, 8)? as usize
+		})
+	}
+}
+
+/// Decompresses a `.lzma` stream: a properties byte, the dictionary size, the uncompressed size and
+/// the range coded data
+pub fn lzma_alone_decompress(buf: &[u8]) -> Result<Vec<u8>> {
+	let header = buf.get(..13).context("lzma header truncated")?;
+	let mut props = u32::from(header[0]);
+	if props >= 9 * 5 * 5 {
+		bail!("invalid lzma properties {props:#x}");
+	}
+	let lc = props % 9;
+	props /= 9;
+	let lp = props % 5;
+	let pb = props / 5;
+	let dict_size = u32::from_le_bytes(header[1..5].try_into()?).max(1 << 12) as usize;
+	let unpack_size = u64::from_le_bytes(header[5..13].try_into()?);
+	let unpack_size = (unpack_size != u64::MAX).then_some(unpack_size as usize);
+
+	let mut rc = RangeDecoder::new(&buf[13..])?;
+	// the size comes from firmware, only preallocate what the stream could plausibly expand to
+	let mut out: Vec<u8> = Vec::with_capacity(unpack_size.unwrap_or(0).min(buf.len() * 64));
+
+	let mut literal_probs = vec![PROB_INIT; 0x300 << (lc + lp)];
+	let mut pos_slot = [[PROB_INIT; 1 << 6]; 4];
+	let mut pos_decoders = [PROB_INIT; 1 + NUM_FULL_DISTANCES - END_POS_MODEL_INDEX as usize];
+	let mut align = [PROB_INIT; 1 << NUM_ALIGN_BITS];
+	let mut len_decoder = LenDecoder::new();
+	let mut rep_len_decoder = LenDecoder::new();
+	let mut is_match = [PROB_INIT; NUM_STATES << 4];
+	let mut is_rep = [PROB_INIT; NUM_STATES];
+	let mut is_rep_g0 = [PROB_INIT; NUM_STATES];
+	let mut is_rep_g1 = [PROB_INIT; NUM_STATES];
+	let mut is_rep_g2 = [PROB_INIT; NUM_STATES];
+	let mut is_rep0_long = [PROB_INIT; NUM_STATES << 4];
+
+	let mut state = 0usize;
+	let mut reps = [0usize; 4];
+	let pb_mask = (1 << pb) - 1;
+	let lp_mask = (1 << lp) - 1;
+
+	while unpack_size.is_none_or(|x| out.len() < x) {
+		let pos_state = out.len() & pb_mask;
+
+		if rc.bit(&mut is_match[(state << 4) + pos_state])? == 0 {
+			let prev = out.last().copied().unwrap_or(0) as usize;
+			let lit_state = ((out.len() & lp_mask) << lc) + (prev >> (8 - lc));
+			let probs = &mut literal_probs[0x300 * lit_state..0x300 * (lit_state + 1)];
+
+			let mut symbol = 1usize;
+			if state >= 7 {
+				let mut match_byte = out[out.len() - reps[0] - 1] as usize;
+				while symbol < 0x100 {
+					let match_bit = (match_byte >> 7) & 1;
+					match_byte <<= 1;
+					let bit = rc.bit(&mut probs[((1 + match_bit) << 8) + symbol])? as usize;
+					symbol = (symbol << 1) | bit;
+					if match_bit != bit {
+						break;
+					}
+				}
+			}
+			while symbol < 0x100 {
+				symbol = (symbol << 1) | rc.bit(&mut probs[symbol])? as usize;
+			}
+			out.push((symbol - 0x100) as u8);
+
+			state = match state {
+				0..4 => 0,
+				4..10 => state - 3,
+				_ => state - 6,
+			};
+			continue;
+		}
+
+		let len;
+		if rc.bit(&mut is_rep[state])? != 0 {
+			if out.is_empty() {
+				bail!("corrupted lzma stream");
+			}
+			if rc.bit(&mut is_rep_g0[state])? == 0 {
+				if rc.bit(&mut is_rep0_long[(state << 4) + pos_state])? == 0 {
+					state = if state < 7 { 9 } else { 11 };
+					out.push(out[out.len() - reps[0] - 1]);
+					continue;
+				}
+			} else {
+				let dist;
+				if rc.bit(&mut is_rep_g1[state])? == 0 {
+					dist = reps[1];
+				} else {
+					if rc.bit(&mut is_rep_g2[state])? == 0 {
+						dist = reps[2];
+					} else {
+						dist = reps[3];
+						reps[3] = reps[2];
+					}
+					reps[2] = reps[1];
+				}
+				reps[1] = reps[0];
+				reps[0] = dist;
+			}
+			len = rep_len_decoder.decode(&mut rc, pos_state)?;
+			state = if state < 7 { 8 } else { 11 };
+		} else {
+			reps[3] = reps[2];
+			reps[2] = reps[1];
+			reps[1] = reps[0];
+			len = len_decoder.decode(&mut rc, pos_state)?;
+			state = if state < 7 { 7 } else { 10 };
+
+			let slot = rc.bit_tree(&mut pos_slot[len.min(3)], 6)?;
+			let dist = if slot < 4 {
+				slot
+			} else {
+				let direct = (slot >> 1) - 1;
+				let base = (2 | (slot & 1)) << direct;
+				if slot < END_POS_MODEL_INDEX {
+					base + rc
+						.bit_tree_reverse(&mut pos_decoders[(base - slot) as usize..], direct)?
+				} else {
+					let high = rc.direct_bits(direct - NUM_ALIGN_BITS as u32)? << NUM_ALIGN_BITS;
+					let low = rc.bit_tree_reverse(&mut align, NUM_ALIGN_BITS as u32)?;
+					base.wrapping_add(high).wrapping_add(low)
+				}
+			};
+			if dist == u32::MAX {
+				// end marker
+				break;
+			}
+			reps[0] = dist as usize;
+			if reps[0] >= dict_size || reps[0] >= out.len() {
+				bail!("corrupted lzma stream");
+			}
+		}
+
+		let len = len + MATCH_MIN_LEN;
+		if unpack_size.is_some_and(|x| out.len() + len > x) {
+			bail!("lzma stream longer than its uncompressed size");
+		}
+		for _ in 0..len {
+			out.push(out[out.len() - reps[0] - 1]);
+		}
+	}
+
+	Ok(out)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::lzma_alone_decompress;
+
+	/// A data vault with the entries `/participants/IETM.D0/_ppd` and `/shared/tables/art`,
+	/// compressed by xz with an unknown size and an end marker
+	pub(crate) const VAULT_LZMA: &[u8] = &[
+		0x5d, 0x00, 0x00, 0x01, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x2a,
+		0xe0, 0x2c, 0x00, 0x02, 0x7a, 0x94, 0x0b, 0x34, 0x0f, 0x95, 0x62, 0xf8, 0x26, 0x24, 0xa6,
+		0x5d, 0x86, 0xc1, 0xfb, 0x8f, 0xfe, 0x47, 0xcf, 0x04, 0xa6, 0xb5, 0x66, 0x16, 0x59, 0x65,
+		0x1d, 0x1e, 0x2b, 0xbc, 0xbf, 0xa2, 0x66, 0x6c, 0x59, 0xee, 0xa0, 0x88, 0xac, 0xa4, 0x41,
+		0x21, 0xa1, 0xa5, 0x1a, 0xc4, 0xc6, 0x83, 0x03, 0x76, 0x06, 0x5c, 0x65, 0xd5, 0x1b, 0x8a,
+		0x00, 0x6e, 0x69, 0xd6, 0xb2, 0xa1, 0xa9, 0x70, 0x35, 0x83, 0x68, 0x13, 0x78, 0xc8, 0x57,
+		0x35, 0x7f, 0x5f, 0xfe, 0x72, 0x24, 0x00,
+	];
+
+	/// `TEXT` compressed by xz with lc=0 lp=2 pb=0, an unknown size and an end marker
+	const TEXT_LZMA: &[u8] = &[
+		0x12, 0x00, 0x00, 0x01, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x38,
+		0x1c, 0xaa, 0x50, 0x26, 0x9a, 0x11, 0x82, 0xa2, 0x9a, 0x05, 0xb0, 0x55, 0x67, 0x73, 0xfc,
+		0xf4, 0xfc, 0x7b, 0x3f, 0xcc, 0x30, 0xd1, 0x15, 0xc6, 0x2f, 0x3f, 0x3f, 0xff, 0xcd, 0x01,
+		0x40, 0x00,
+	];
+
+	fn text() -> Vec<u8> {
+		b"powerd powerd powerd thermal thermal power ".repeat(4)
+	}
+
+	fn with_size(stream: &[u8], size: u64) -> Vec<u8> {
+		let mut stream = stream.to_vec();
+		stream[5..13].copy_from_slice(&size.to_le_bytes());
+		stream
+	}
+
+	#[test]
+	fn end_marker() {
+		assert_eq!(lzma_alone_decompress(TEXT_LZMA).unwrap(), text());
+	}
+
+	#[test]
+	fn known_size() {
+		let size = text().len() as u64;
+		assert_eq!(
+			lzma_alone_decompress(&with_size(TEXT_LZMA, size)).unwrap(),
+			text()
+		);
+	}
+
+	#[test]
+	fn vault() {
+		let vault = lzma_alone_decompress(VAULT_LZMA).unwrap();
+		assert_eq!(vault.len(), 110);
+		assert_eq!(vault[..2], [0x55, 0x55]);
+	}
+
+	#[test]
+	fn truncated() {
+		assert!(lzma_alone_decompress(&TEXT_LZMA[..TEXT_LZMA.len() / 2]).is_err());
+		assert!(lzma_alone_decompress(&TEXT_LZMA[..10]).is_err());
+	}
+
+	#[test]
+	fn huge_size() {
+		assert!(lzma_alone_decompress(&with_size(&TEXT_LZMA[..18], 1 << 62)).is_err());
+	}
+
+	#[test]
+	fn invalid_properties() {
+		let mut stream = TEXT_LZMA.to_vec();
+		stream[0] = 9 * 5 * 5;
+		assert!(lzma_alone_decompress(&stream).is_err());
+	}
+}
@@ -6,22 +6,29 @@ use std::{
 	},
 	path::PathBuf,
 	process::exit,
+	time::Duration,
 };
 
-use anyhow::{Context, Result};
-use clap::{Parser, ValueEnum};
+use anyhow::{Context, Result, bail};
+use clap::{Parser, Subcommand, ValueEnum};
 use log::{LevelFilter, info};
 use serde::{Deserialize, Serialize};
 
 use crate::{
 	daemon::{DaemonConfig, daemon},
+	msr::msr_allow_unsafe,
 	sensors::{
 		SensorConfig, SensorInfo,
+		dptf_gddv::dptf_dump,
+		hwmon::SensorReadings,
+		intel_dptf::dptf_add_hids,
+		power_monitor::power_monitor,
 		throttle::{cpu_throttling, graphics_throttling, ring_throttling},
 	},
 };
 
 mod daemon;
+mod lzma;
 mod msr;
 mod ppd;
 mod sensors;
@@ -52,10 +59,17 @@ enum Action {
 	Restore,
 	/// Print throttling info from CPU
 	ThrottleInfo { targets: Vec<ThrottleTarget> },
+	/// Print the DPTF policies and the decoded DPTF data vault
+	DptfDump,
+	/// Print RAPL power and battery discharge until interrupted
+	Monitor {
+		/// Seconds between samples
+		#[arg(default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+		interval: u64,
+	},
 }
 
-#[derive(Parser)]
-/// Intel laptop cooling/CPU tweaking tool
+#[derive(Subcommand)]
 enum Cli {
 	#[clap(flatten)]
 	Action(Action),
@@ -69,15 +83,38 @@ enum Cli {
 	},
 }
 
+#[derive(Parser)]
+/// Intel laptop cooling/CPU tweaking tool
+struct Args {
+	/// Allow writing MSRs that are marked as dangerous (e.g. BD PROCHOT)
+	#[arg(long, global = true)]
+	allow_unsafe_msr: bool,
+	/// Additional ACPI HID of the DPTF (int3400) device, can be repeated
+	#[arg(long = "dptf-hid", global = true)]
+	dptf_hids: Vec<String>,
+	#[command(subcommand)]
+	command: Cli,
+}
+
 fn main() -> Result<()> {
 	env_logger::builder()
 		.filter_level(LevelFilter::Trace)
 		.parse_default_env()
 		.init();
 
-	let args = Cli::parse();
+	let args = Args::parse();
+	// the daemon does the writes for client actions, so these would silently do nothing
+	if matches!(args.command, Cli::Action(_))
+		&& (args.allow_unsafe_msr || !args.dptf_hids.is_empty())
+	{
+		bail!(
+			"--allow-unsafe-msr and --dptf-hid only apply to `root` actions and the daemon, pass them when starting the daemon instead"
+		);
+	}
+	msr_allow_unsafe(args.allow_unsafe_msr);
+	dptf_add_hids(&args.dptf_hids);
 
-	match args {
+	match args.command {
 		Cli::Daemon { config } => {
 			info!("starting daemon");
 
@@ -91,6 +128,7 @@ fn main() -> Result<()> {
 		Cli::Root(action) => match action {
 			Action::Info => {
 				println!("{}", SensorInfo::read()?);
+				println!("{}", SensorReadings::read()?);
 			}
 			Action::Dump => {
 				println!(
@@ -104,9 +142,7 @@ fn main() -> Result<()> {
 				)
 				.context("failed to deserialize config")?;
 
-				let mut info = SensorInfo::read().context("failed to read current sensor data")?;
-				cfg.apply(&mut info).context("failed to apply config")?;
-				info.write().context("failed to write config")?;
+				cfg.write(true)?;
 
 				let info = SensorInfo::read()?;
 				println!("{info}");
@@ -115,6 +151,12 @@ fn main() -> Result<()> {
 				println!("restoring automatic profile management requires the daemon");
 				exit(1);
 			}
+			Action::DptfDump => {
+				println!("{}", dptf_dump()?);
+			}
+			Action::Monitor { interval } => {
+				power_monitor(&mut stdout(), Duration::from_secs(interval))?;
+			}
 			Action::ThrottleInfo { targets } => {
 				for target in targets {
 					println!(
@@ -2,18 +2,58 @@ use std::{
 	fs::{File, OpenOptions},
 	ops::RangeInclusive,
 	os::unix::fs::FileExt,
+	sync::atomic::{AtomicBool, Ordering},
 };
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
+use log::info;
+
+static ALLOW_UNSAFE_MSR: AtomicBool = AtomicBool::new(false);
 
 #[repr(u32)]
+#[derive(Copy, Clone, Debug)]
 pub enum Msr {
+	PkgCstConfigControl = 0xE2,
 	PowerCtl = 0x1FC,
 	ConfigTdpControl = 0x64B,
 	CpuPerfLimitReasons = 0x64F,
 	GraphicsPerfLimitReasons = 0x6B0,
 	RingPerfLimitReasons = 0x6B1,
 }
+impl Msr {
+	/// Bits that powerd is allowed to change, everything else is treated as reserved
+	fn writable_mask(self) -> u64 {
+		match self {
+			// package C-state limit
+			Self::PkgCstConfigControl => 0xF,
+			// BD PROCHOT, C1E enable
+			Self::PowerCtl => 0b11,
+			// TDP level
+			Self::ConfigTdpControl => 0b11,
+			Self::CpuPerfLimitReasons
+			| Self::GraphicsPerfLimitReasons
+			| Self::RingPerfLimitReasons => 0,
+		}
+	}
+
+	/// Bit that, when set, means the register can't be changed until reset
+	fn lock_bit(self) -> Option<usize> {
+		match self {
+			Self::PkgCstConfigControl => Some(15),
+			Self::ConfigTdpControl => Some(31),
+			_ => None,
+		}
+	}
+
+	/// Registers that can damage hardware or cause instability if set wrong
+	fn dangerous(self) -> bool {
+		matches!(self, Self::PowerCtl)
+	}
+}
+
+pub fn msr_allow_unsafe(allow: bool) {
+	ALLOW_UNSAFE_MSR.store(allow, Ordering::Relaxed);
+}
 
 fn msr_open(cpu: usize) -> Result<File> {
 	OpenOptions::new()
@@ -23,20 +63,47 @@ fn msr_open(cpu: usize) -> Result<File> {
 		.context("failed to open msr")
 }
 
-pub fn msr_read(cpu: usize, reg: Msr) -> Result<u64> {
+fn msr_read_file(msr: &File, reg: Msr) -> Result<u64> {
 	let mut buf = [0; 8];
 
-	let msr = msr_open(cpu)?;
 	msr.read_exact_at(&mut buf, reg as u64)
 		.context("failed to read msr")?;
 
 	Ok(u64::from_ne_bytes(buf))
 }
 
-pub fn msr_write(cpu: usize, reg: Msr, val: u64) -> Result<()> {
-	let buf = u64::to_ne_bytes(val);
+pub fn msr_read(cpu: usize, reg: Msr) -> Result<u64> {
+	msr_read_file(&msr_open(cpu)?, reg)
+}
 
+pub fn msr_write(cpu: usize, reg: Msr, val: u64) -> Result<()> {
 	let msr = msr_open(cpu)?;
+	let old = msr_read_file(&msr, reg)?;
+
+	if old == val {
+		return Ok(());
+	}
+
+	if let Some(lock) = reg.lock_bit()
+		&& msr_get_bit(old, lock)
+	{
+		bail!("refusing to write msr {reg:?} on cpu {cpu}: register is locked");
+	}
+
+	let reserved = (old ^ val) & !reg.writable_mask();
+	if reserved != 0 {
+		bail!(
+			"refusing to write msr {reg:?} on cpu {cpu}: would change reserved bits {reserved:#x}"
+		);
+	}
+
+	if reg.dangerous() && !ALLOW_UNSAFE_MSR.load(Ordering::Relaxed) {
+		bail!("refusing to write msr {reg:?} on cpu {cpu}: pass --allow-unsafe-msr to allow this");
+	}
+
+	info!("msr {reg:?} on cpu {cpu}: {old:#x} -> {val:#x}");
+
+	let buf = u64::to_ne_bytes(val);
 	msr.write_all_at(&buf, reg as u64)
 		.context("failed to write msr")?;
 
@@ -52,19 +119,17 @@ pub fn msr_get_bit(val: u64, bit: usize) -> bool {
 	((val >> bit) & 1) == 1
 }
 
-#[allow(dead_code)]
 pub fn msr_get_bits(msr: u64, bits: RangeInclusive<u32>) -> u64 {
 	let start = *bits.start();
 	let mask: u64 = bits.map(|x| 1u64 << x).sum();
 	(msr & mask) >> start
 }
-#[allow(dead_code)]
 pub fn msr_set_bits(mut msr: u64, bits: RangeInclusive<u32>, mut val: u64) -> u64 {
 	let start = *bits.start();
 	let mask: u64 = bits.map(|x| 2u64.pow(x)).sum();
 	msr &= !mask;
 	val <<= start;
-	val &= !mask;
+	val &= mask;
 
 	msr | val
 }
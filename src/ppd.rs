@@ -215,9 +215,10 @@ impl PpdState {
 		let powerd = profile.into_powerd(&self.cfg.ppd);
 
 		let mut current = self.state.lock().unwrap();
-		let state = apply_cfg_from_file(&self.cfg.profiles, powerd)?;
+		let state = apply_cfg_from_file(&self.cfg.profiles, powerd, true)?;
 		current.ppd_set = true;
 		current.ppd_profile = state.cfg.ppd_name;
+		current.last_applied = Some(state.path.clone());
 		if from_hold {
 			current.held.replace(state);
 		} else {
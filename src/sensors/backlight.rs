@@ -0,0 +1,227 @@
+use std::{
+	fmt::Display,
+	path::{Path, PathBuf},
+	str::FromStr,
+};
+
+use anyhow::{Context, Result, bail};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::sysfs::{sysfs_exists, sysfs_list, sysfs_read, sysfs_write};
+
+const BACKLIGHT_ROOT: &str = "class/backlight";
+const LEDS_ROOT: &str = "class/leds";
+
+/// A backlight or LED under `/sys/class/backlight` or `/sys/class/leds`
+#[derive(Clone, Debug)]
+pub struct BrightnessInfo {
+	path: PathBuf,
+	/// e.g. `intel_backlight` or `tpacpi::kbd_backlight`
+	pub name: String,
+	pub max_brightness: u64,
+	current: u64,
+	pub brightness: u64,
+}
+impl BrightnessInfo {
+	fn read(path: PathBuf, name: String) -> Result<Self> {
+		let brightness = sysfs_read(&path.join("brightness"))?;
+
+		Ok(Self {
+			name,
+			max_brightness: sysfs_read(&path.join("max_brightness"))?,
+			current: brightness,
+			brightness,
+			path,
+		})
+	}
+
+	fn read_class(root: &str) -> Result<Vec<Self>> {
+		let root = Path::new(root);
+		if !sysfs_exists(root)? {
+			return Ok(Vec::new());
+		}
+
+		let mut devices = Vec::new();
+		for name in sysfs_list(root)? {
+			// some input and acpi leds fail reads with EIO or ENODATA
+			match Self::read(root.join(&name), name.clone()) {
+				Ok(x) => devices.push(x),
+				Err(err) => warn!("skipping {name}: {err:?}"),
+			}
+		}
+
+		Ok(devices)
+	}
+
+	pub fn read_backlights() -> Result<Vec<Self>> {
+		Self::read_class(BACKLIGHT_ROOT)
+	}
+
+	pub fn read_leds() -> Result<Vec<Self>> {
+		Self::read_class(LEDS_ROOT)
+	}
+
+	/// Only writes changed values, the config decides whether a reapply overrides the brightness
+	/// keys
+	pub fn write(&self) -> Result<()> {
+		if self.brightness != self.current {
+			sysfs_write(&self.path.join("brightness"), self.brightness)?;
+		}
+
+		Ok(())
+	}
+}
+impl Display for BrightnessInfo {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"{}: {}/{}",
+			self.name, self.brightness, self.max_brightness
+		)
+	}
+}
+
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum BrightnessRepr {
+	Absolute(u64),
+	Percent(String),
+}
+
+/// An absolute brightness, or a percentage of `max_brightness` like `"40%"`
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "BrightnessRepr", into = "BrightnessRepr")]
+pub enum Brightness {
+	Absolute(u64),
+	Percent(u64),
+}
+impl Brightness {
+	fn resolve(self, max: u64) -> u64 {
+		match self {
+			Self::Absolute(brightness) => brightness,
+			// rounds up so a nonzero percentage never turns the device off
+			Self::Percent(pct) => (max * pct).div_ceil(100),
+		}
+	}
+}
+impl FromStr for Brightness {
+	type Err = anyhow::Error;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let pct = s
+			.trim()
+			.strip_suffix('%')
+			.with_context(|| format!("invalid brightness {s:?}, expected e.g. 40%"))?
+			.trim()
+			.parse()
+			.with_context(|| format!("invalid brightness percentage {s:?}"))?;
+		if pct > 100 {
+			bail!("invalid brightness percentage {s:?}, expected at most 100%");
+		}
+
+		Ok(Self::Percent(pct))
+	}
+}
+impl TryFrom<BrightnessRepr> for Brightness {
+	type Error = anyhow::Error;
+	fn try_from(value: BrightnessRepr) -> Result<Self, Self::Error> {
+		match value {
+			BrightnessRepr::Absolute(brightness) => Ok(Self::Absolute(brightness)),
+			BrightnessRepr::Percent(brightness) => brightness.parse(),
+		}
+	}
+}
+impl From<Brightness> for BrightnessRepr {
+	fn from(value: Brightness) -> Self {
+		match value {
+			Brightness::Absolute(brightness) => Self::Absolute(brightness),
+			Brightness::Percent(pct) => Self::Percent(format!("{pct}%")),
+		}
+	}
+}
+
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct BrightnessConfig {
+	/// Device name under `/sys/class/backlight` or `/sys/class/leds`
+	pub name: String,
+	pub brightness: Brightness,
+	/// Only set the brightness when switching to this profile, not every time the daemon
+	/// reapplies it, so the brightness keys keep working in between
+	#[serde(default = "default_only_on_transition")]
+	pub only_on_transition: bool,
+}
+fn default_only_on_transition() -> bool {
+	true
+}
+impl BrightnessConfig {
+	pub fn apply(&self, devices: &mut [BrightnessInfo], transition: bool) -> Result<()> {
+		let device = devices
+			.iter_mut()
+			.find(|x| x.name == self.name)
+			.with_context(|| format!("failed to find brightness device {:?}", self.name))?;
+
+		if self.only_on_transition && !transition {
+			return Ok(());
+		}
+
+		let brightness = self.brightness.resolve(device.max_brightness);
+		if brightness > device.max_brightness {
+			bail!(
+				"invalid brightness {brightness} for {:?}, expected at most {}",
+				self.name,
+				device.max_brightness
+			);
+		}
+		device.brightness = brightness;
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Brightness, BrightnessConfig};
+
+	#[test]
+	fn parse() {
+		assert_eq!(
+			"40%".parse::<Brightness>().unwrap(),
+			Brightness::Percent(40)
+		);
+		assert_eq!(
+			" 100 % ".parse::<Brightness>().unwrap(),
+			Brightness::Percent(100)
+		);
+		assert!("101%".parse::<Brightness>().is_err());
+		assert!("40".parse::<Brightness>().is_err());
+		assert!("-1%".parse::<Brightness>().is_err());
+	}
+
+	#[test]
+	fn resolve() {
+		assert_eq!(Brightness::Absolute(7).resolve(100), 7);
+		assert_eq!(Brightness::Percent(40).resolve(19200), 7680);
+		assert_eq!(Brightness::Percent(1).resolve(2), 1);
+		assert_eq!(Brightness::Percent(0).resolve(2), 0);
+		assert_eq!(Brightness::Percent(100).resolve(255), 255);
+	}
+
+	#[test]
+	fn config() {
+		let cfg: BrightnessConfig =
+			serde_json::from_str(r#"{ "name": "intel_backlight", "brightness": "40%" }"#).unwrap();
+		assert_eq!(cfg.brightness, Brightness::Percent(40));
+		assert!(cfg.only_on_transition);
+		assert_eq!(
+			serde_json::to_value(Brightness::Percent(40)).unwrap(),
+			serde_json::json!("40%")
+		);
+
+		let cfg: BrightnessConfig = serde_json::from_str(
+			r#"{ "name": "tpacpi::kbd_backlight", "brightness": 0, "only_on_transition": false }"#,
+		)
+		.unwrap();
+		assert_eq!(cfg.brightness, Brightness::Absolute(0));
+		assert!(!cfg.only_on_transition);
+	}
+}
@@ -0,0 +1,430 @@
+use std::{
+	fmt::Display,
+	path::{Path, PathBuf},
+};
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::sysfs::{sysfs_exists, sysfs_list, sysfs_read_opt, sysfs_write};
+
+const POWER_SUPPLY_ROOT: &str = "class/power_supply";
+
+/// Lists the system's power supplies of the given types, skipping peripherals like mice
+fn power_supplies(types: &[&str]) -> Result<Vec<(PathBuf, String)>> {
+	let root = Path::new(POWER_SUPPLY_ROOT);
+	if !sysfs_exists(root)? {
+		return Ok(Vec::new());
+	}
+
+	let mut supplies = Vec::new();
+	for name in sysfs_list(root)? {
+		let path = root.join(&name);
+		if let Some(ty) = sysfs_read_opt::<String>(&path.join("type"))?
+			&& types.contains(&ty.as_str())
+			&& sysfs_read_opt::<String>(&path.join("scope"))?.as_deref() != Some("Device")
+		{
+			supplies.push((path, name));
+		}
+	}
+
+	Ok(supplies)
+}
+
+/// Parses a sysfs choice list like `[auto] inhibit-charge force-discharge`
+fn parse_choices(list: &str) -> (Option<String>, Vec<String>) {
+	let mut current = None;
+	let choices = list
+		.split_whitespace()
+		.map(
+			|x| match x.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+				Some(x) => {
+					current = Some(x.to_string());
+					x.to_string()
+				}
+				None => x.to_string(),
+			},
+		)
+		.collect();
+
+	(current, choices)
+}
+
+#[derive(Clone, Debug)]
+struct ChargeControl {
+	start_threshold: Option<u8>,
+	end_threshold: Option<u8>,
+	behaviour: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct BatteryInfo {
+	path: PathBuf,
+	/// e.g. `BAT0`
+	pub name: String,
+	pub status: Option<String>,
+	/// Percent
+	pub capacity: Option<u8>,
+	/// Full and design capacity in µWh, or µAh on batteries that only report charge
+	pub full: Option<u64>,
+	pub full_design: Option<u64>,
+	pub cycle_count: Option<u64>,
+	/// µW, from `power_now` or `current_now` × `voltage_now`
+	pub power: Option<u64>,
+
+	pub available_behaviours: Vec<String>,
+	current: ChargeControl,
+	/// Percent
+	pub start_threshold: Option<u8>,
+	/// Percent
+	pub end_threshold: Option<u8>,
+	pub charge_behaviour: Option<String>,
+}
+impl BatteryInfo {
+	fn read(path: PathBuf, name: String) -> Result<Self> {
+		let read_u64 = |file: &str| -> Result<Option<u64>> { sysfs_read_opt(&path.join(file)) };
+
+		let power = match read_u64("power_now")? {
+			Some(power) => Some(power),
+			// current_now is negative on some batteries while discharging
+			None => match (
+				sysfs_read_opt::<i64>(&path.join("current_now"))?,
+				read_u64("voltage_now")?,
+			) {
+				(Some(current), Some(voltage)) => Some(current.unsigned_abs() * voltage / 1000000),
+				_ => None,
+			},
+		};
+
+		let (charge_behaviour, available_behaviours) =
+			match sysfs_read_opt::<String>(&path.join("charge_behaviour"))? {
+				Some(list) => parse_choices(&list),
+				None => (None, Vec::new()),
+			};
+		let current = ChargeControl {
+			start_threshold: sysfs_read_opt(&path.join("charge_control_start_threshold"))?,
+			end_threshold: sysfs_read_opt(&path.join("charge_control_end_threshold"))?,
+			behaviour: charge_behaviour,
+		};
+
+		Ok(Self {
+			name,
+			status: sysfs_read_opt(&path.join("status"))?,
+			capacity: sysfs_read_opt(&path.join("capacity"))?,
+			full: read_u64("energy_full")?.or(read_u64("charge_full")?),
+			full_design: read_u64("energy_full_design")?.or(read_u64("charge_full_design")?),
+			// some firmware reports 0 when it doesn't count cycles
+			cycle_count: read_u64("cycle_count")?.filter(|x| *x != 0),
+			power,
+
+			available_behaviours,
+			start_threshold: current.start_threshold,
+			end_threshold: current.end_threshold,
+			charge_behaviour: current.behaviour.clone(),
+			current,
+			path,
+		})
+	}
+
+	pub fn read_all() -> Result<Vec<Self>> {
+		power_supplies(&["Battery"])?
+			.into_iter()
+			.map(|(path, name)| Self::read(path, name))
+			.collect()
+	}
+
+	pub fn discharging(&self) -> bool {
+		self.status.as_deref() == Some("Discharging")
+	}
+
+	/// Full capacity relative to the design capacity in percent
+	pub fn health(&self) -> Option<f64> {
+		match (self.full, self.full_design) {
+			(Some(full), Some(design)) if design != 0 => Some(full as f64 / design as f64 * 100.0),
+			_ => None,
+		}
+	}
+
+	fn write_start(&self) -> Result<()> {
+		match self.start_threshold {
+			Some(start) if self.current.start_threshold != Some(start) => {
+				sysfs_write(&self.path.join("charge_control_start_threshold"), start)
+			}
+			_ => Ok(()),
+		}
+	}
+
+	fn write_end(&self) -> Result<()> {
+		match self.end_threshold {
+			Some(end) if self.current.end_threshold != Some(end) => {
+				sysfs_write(&self.path.join("charge_control_end_threshold"), end)
+			}
+			_ => Ok(()),
+		}
+	}
+
+	pub fn write(&self) -> Result<()> {
+		// the start threshold has to stay below the end threshold, so a start threshold at or
+		// above the current end threshold can only be written after the new end threshold
+		if self
+			.start_threshold
+			.zip(self.current.end_threshold)
+			.is_some_and(|(start, end)| start >= end)
+		{
+			self.write_end()?;
+			self.write_start()?;
+		} else {
+			self.write_start()?;
+			self.write_end()?;
+		}
+
+		if let Some(behaviour) = &self.charge_behaviour
+			&& self.current.behaviour.as_ref() != Some(behaviour)
+		{
+			sysfs_write(&self.path.join("charge_behaviour"), behaviour)?;
+		}
+
+		Ok(())
+	}
+}
+impl Display for BatteryInfo {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "Battery {}", self.name)?;
+		if let Some(status) = &self.status {
+			write!(f, " ({status})")?;
+		}
+		if let Some(capacity) = self.capacity {
+			write!(f, ": {capacity}%")?;
+		}
+		if let Some(power) = self.power {
+			write!(f, " at {:.2}W", power as f64 / 1000000.0)?;
+		}
+		if let Some(health) = self.health() {
+			write!(f, ", health {health:.1}%")?;
+		}
+		if let Some(cycles) = self.cycle_count {
+			write!(f, ", {cycles} cycles")?;
+		}
+		if self.start_threshold.is_some() || self.end_threshold.is_some() {
+			write!(
+				f,
+				"\n\tcharge thresholds: {}-{}%",
+				self.start_threshold
+					.map(|x| x.to_string())
+					.unwrap_or_default(),
+				self.end_threshold
+					.map(|x| x.to_string())
+					.unwrap_or_default()
+			)?;
+		}
+		if let Some(behaviour) = &self.charge_behaviour {
+			write!(
+				f,
+				"\n\tcharge behaviour: {behaviour} (available: {})",
+				self.available_behaviours.join(", ")
+			)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// Total power drawn from every discharging battery in µW, `None` if none is discharging
+pub fn battery_discharge(batteries: &[BatteryInfo]) -> Option<u64> {
+	batteries
+		.iter()
+		.filter(|x| x.discharging())
+		.map(|x| x.power)
+		.reduce(|a, b| Some(a? + b?))?
+}
+
+#[derive(Clone, Debug)]
+pub struct AcAdapterInfo {
+	/// e.g. `AC` or `ucsi-source-psy-USBC000:001`
+	pub name: String,
+	pub online: bool,
+}
+impl AcAdapterInfo {
+	pub fn read_all() -> Result<Vec<Self>> {
+		power_supplies(&["Mains", "USB"])?
+			.into_iter()
+			.map(|(path, name)| {
+				Ok(Self {
+					online: sysfs_read_opt::<u8>(&path.join("online"))? == Some(1),
+					name,
+				})
+			})
+			.collect()
+	}
+}
+impl Display for AcAdapterInfo {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"AC adapter {}: {}",
+			self.name,
+			if self.online { "online" } else { "offline" }
+		)
+	}
+}
+
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct BatteryConfig {
+	/// Every battery if unset
+	#[serde(default)]
+	pub name: Option<String>,
+	/// Percent, charging starts below this
+	#[serde(default)]
+	pub start_threshold: Option<u8>,
+	/// Percent, charging stops at this
+	#[serde(default)]
+	pub end_threshold: Option<u8>,
+	/// e.g. `auto`, `inhibit-charge` or `force-discharge`
+	#[serde(default)]
+	pub charge_behaviour: Option<String>,
+}
+impl BatteryConfig {
+	pub fn apply(&self, batteries: &mut [BatteryInfo]) -> Result<()> {
+		let mut found = false;
+
+		for battery in batteries
+			.iter_mut()
+			.filter(|x| self.name.as_ref().is_none_or(|name| *name == x.name))
+		{
+			if let Some(start) = self.start_threshold {
+				if start > 100 {
+					bail!("invalid charge start threshold {start}%");
+				}
+				if battery.start_threshold.is_none() {
+					bail!("battery {} has no charge start threshold", battery.name);
+				}
+				battery.start_threshold = Some(start);
+			}
+			if let Some(end) = self.end_threshold {
+				if end > 100 {
+					bail!("invalid charge end threshold {end}%");
+				}
+				if battery.end_threshold.is_none() {
+					bail!("battery {} has no charge end threshold", battery.name);
+				}
+				battery.end_threshold = Some(end);
+			}
+			if let (Some(start), Some(end)) = (battery.start_threshold, battery.end_threshold)
+				&& start >= end
+			{
+				bail!(
+					"invalid charge thresholds {start}-{end}% for battery {}",
+					battery.name
+				);
+			}
+
+			if let Some(behaviour) = &self.charge_behaviour {
+				if battery.available_behaviours.is_empty() {
+					bail!("battery {} has no charge behaviour", battery.name);
+				}
+				if !battery.available_behaviours.contains(behaviour) {
+					bail!(
+						"invalid charge behaviour {behaviour:?} for battery {}, expected one of {}",
+						battery.name,
+						battery.available_behaviours.join(", ")
+					);
+				}
+				battery.charge_behaviour = Some(behaviour.clone());
+			}
+
+			found = true;
+		}
+
+		if !found {
+			match &self.name {
+				Some(name) => bail!("failed to find battery {name}"),
+				None => bail!("failed to find a battery"),
+			}
+		}
+
+		Ok(())
+	}
+}
+impl From<BatteryInfo> for BatteryConfig {
+	fn from(value: BatteryInfo) -> Self {
+		Self {
+			name: Some(value.name),
+			start_threshold: value.start_threshold,
+			end_threshold: value.end_threshold,
+			charge_behaviour: value.charge_behaviour,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::PathBuf;
+
+	use super::{BatteryInfo, ChargeControl, battery_discharge, parse_choices};
+
+	fn battery(status: &str, power: Option<u64>) -> BatteryInfo {
+		BatteryInfo {
+			path: PathBuf::new(),
+			name: "BAT0".to_string(),
+			status: Some(status.to_string()),
+			capacity: None,
+			full: None,
+			full_design: None,
+			cycle_count: None,
+			power,
+			available_behaviours: Vec::new(),
+			current: ChargeControl {
+				start_threshold: None,
+				end_threshold: None,
+				behaviour: None,
+			},
+			start_threshold: None,
+			end_threshold: None,
+			charge_behaviour: None,
+		}
+	}
+
+	#[test]
+	fn choices() {
+		assert_eq!(
+			parse_choices("[auto] inhibit-charge force-discharge"),
+			(
+				Some("auto".to_string()),
+				vec![
+					"auto".to_string(),
+					"inhibit-charge".to_string(),
+					"force-discharge".to_string()
+				]
+			)
+		);
+		assert_eq!(
+			parse_choices("auto inhibit-charge"),
+			(None, vec!["auto".to_string(), "inhibit-charge".to_string()])
+		);
+		assert_eq!(parse_choices(""), (None, Vec::new()));
+	}
+
+	#[test]
+	fn discharge() {
+		assert_eq!(battery_discharge(&[]), None);
+		assert_eq!(
+			battery_discharge(&[battery("Charging", Some(20000000))]),
+			None
+		);
+		assert_eq!(
+			battery_discharge(&[
+				battery("Discharging", Some(8000000)),
+				battery("Full", Some(0)),
+				battery("Discharging", Some(2500000)),
+			]),
+			Some(10500000)
+		);
+		assert_eq!(
+			battery_discharge(&[
+				battery("Discharging", Some(8000000)),
+				battery("Discharging", None),
+			]),
+			None
+		);
+	}
+}
@@ -0,0 +1,154 @@
+use std::{
+	fmt::Display,
+	path::{Path, PathBuf},
+	sync::Mutex,
+};
+
+use anyhow::{Result, bail};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::sysfs::{sysfs_exists, sysfs_list, sysfs_read, sysfs_write};
+
+const THERMAL_ROOT: &str = "class/thermal";
+
+/// Cooling devices powerd changed, with their `cur_state` from before powerd first wrote them
+static CONTROLLED_DEVICES: Mutex<Vec<(PathBuf, u64)>> = Mutex::new(Vec::new());
+
+/// Hands every cooling device `keep` doesn't match back in the state it was in before powerd
+/// changed it
+fn cooling_devices_restore_unless(keep: impl Fn(&Path) -> bool) {
+	CONTROLLED_DEVICES
+		.lock()
+		.unwrap()
+		.retain(|(path, original)| {
+			if keep(path) {
+				return true;
+			}
+			info!("restoring cooling device {path:?} to state {original}");
+			if let Err(err) = sysfs_write(&path.join("cur_state"), original) {
+				warn!("failed to restore cooling device {path:?}: {err:?}");
+			}
+			false
+		});
+}
+
+/// Hands every cooling device powerd changed back in the state it was in before
+pub fn cooling_devices_restore() {
+	cooling_devices_restore_unless(|_| false);
+}
+
+/// Writes the configured cooling devices and restores every other device powerd changed, e.g.
+/// when switching to a profile without `cooling_devices`
+pub fn cooling_devices_write(devices: &[CoolingDeviceInfo]) -> Result<()> {
+	for device in devices {
+		device.write()?;
+	}
+
+	cooling_devices_restore_unless(|path| devices.iter().any(|x| x.configured && x.path == path));
+
+	Ok(())
+}
+
+#[derive(Clone, Debug)]
+pub struct CoolingDeviceInfo {
+	path: PathBuf,
+	pub id: usize,
+	/// e.g. `intel_powerclamp`, `Processor` or `TCC Offset`
+	pub device_type: String,
+	pub max_state: u64,
+	current: u64,
+	pub state: u64,
+	/// Whether the applied config sets this device
+	configured: bool,
+}
+impl CoolingDeviceInfo {
+	fn read(path: PathBuf, id: usize) -> Result<Self> {
+		let state = sysfs_read(&path.join("cur_state"))?;
+
+		Ok(Self {
+			id,
+			device_type: sysfs_read(&path.join("type"))?,
+			max_state: sysfs_read(&path.join("max_state"))?,
+			current: state,
+			state,
+			configured: false,
+			path,
+		})
+	}
+
+	pub fn read_all() -> Result<Vec<Self>> {
+		let root = Path::new(THERMAL_ROOT);
+		if !sysfs_exists(root)? {
+			return Ok(Vec::new());
+		}
+
+		let mut devices: Vec<(usize, String)> = sysfs_list(root)?
+			.into_iter()
+			.filter_map(|x| Some((x.strip_prefix("cooling_device")?.parse().ok()?, x)))
+			.collect();
+		devices.sort_unstable();
+
+		devices
+			.into_iter()
+			.map(|(id, name)| Self::read(root.join(name), id))
+			.collect()
+	}
+
+	/// Only writes changed states, thermal governors may be driving the others
+	fn write(&self) -> Result<()> {
+		if self.configured && self.state != self.current {
+			let mut devices = CONTROLLED_DEVICES.lock().unwrap();
+			if !devices.iter().any(|(path, _)| *path == self.path) {
+				devices.push((self.path.clone(), self.current));
+			}
+			drop(devices);
+
+			sysfs_write(&self.path.join("cur_state"), self.state)?;
+		}
+
+		Ok(())
+	}
+}
+impl Display for CoolingDeviceInfo {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"Cooling device {} \"{}\": state {}/{}",
+			self.id, self.device_type, self.state, self.max_state
+		)
+	}
+}
+
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct CoolingDeviceConfig {
+	/// `type` of the cooling device, every device with that type is changed
+	pub device: String,
+	pub state: u64,
+}
+impl CoolingDeviceConfig {
+	pub fn apply(&self, devices: &mut [CoolingDeviceInfo]) -> Result<()> {
+		let mut found = false;
+
+		for device in devices.iter_mut().filter(|x| x.device_type == self.device) {
+			if self.state > device.max_state {
+				bail!(
+					"invalid state {} for cooling device {} {:?}, expected at most {}",
+					self.state,
+					device.id,
+					self.device,
+					device.max_state
+				);
+			}
+			device.state = self.state;
+			device.configured = true;
+			found = true;
+		}
+
+		if !found {
+			bail!("failed to find cooling device {:?}", self.device);
+		}
+
+		Ok(())
+	}
+}
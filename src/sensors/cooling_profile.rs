@@ -1,44 +1,218 @@
-use std::{fmt::Display, path::Path};
+use std::{
+	fmt::Display,
+	path::{Path, PathBuf},
+};
 
-use anyhow::Result;
+use anyhow::{Result, bail};
+use log::warn;
 use serde::{Deserialize, Serialize};
 
-use crate::sysfs::{sysfs_read, sysfs_write};
+use crate::sysfs::{sysfs_exists, sysfs_list, sysfs_read, sysfs_write};
+
+const LEGACY_ROOT: &str = "firmware/acpi";
+const CLASS_ROOT: &str = "class/platform-profile";
+
+#[derive(Debug, Clone)]
+pub struct PlatformProfileInfo {
+	path: PathBuf,
+	/// `None` for the legacy `firmware/acpi/platform_profile` interface
+	pub name: Option<String>,
+	current: String,
+	/// Whether a config set this handler directly rather than through the legacy interface
+	configured: bool,
+	pub profile: String,
+	pub choices: Vec<String>,
+}
+impl PlatformProfileInfo {
+	fn read_legacy() -> Result<Option<Self>> {
+		let path = PathBuf::from(LEGACY_ROOT);
+		if !sysfs_exists(&path.join("platform_profile"))? {
+			return Ok(None);
+		}
+
+		let profile: String = sysfs_read(&path.join("platform_profile"))?;
+		Ok(Some(Self {
+			choices: sysfs_read::<String>(&path.join("platform_profile_choices"))?
+				.split_whitespace()
+				.map(ToString::to_string)
+				.collect(),
+			path: path.join("platform_profile"),
+			name: None,
+			current: profile.clone(),
+			configured: false,
+			profile,
+		}))
+	}
+
+	fn read_handler(path: PathBuf) -> Result<Self> {
+		let profile: String = sysfs_read(&path.join("profile"))?;
+		Ok(Self {
+			name: Some(sysfs_read(&path.join("name"))?),
+			choices: sysfs_read::<String>(&path.join("choices"))?
+				.split_whitespace()
+				.map(ToString::to_string)
+				.collect(),
+			path: path.join("profile"),
+			current: profile.clone(),
+			configured: false,
+			profile,
+		})
+	}
+
+	fn set(&mut self, profile: &str) -> Result<()> {
+		// the legacy interface reads "custom" when handlers disagree, which can't be written back
+		if profile != self.current && !self.choices.iter().any(|x| x == profile) {
+			bail!(
+				"invalid platform profile {profile:?} for {}, expected one of {}",
+				self.name.as_deref().unwrap_or("firmware/acpi"),
+				self.choices.join(", ")
+			);
+		}
+		profile.clone_into(&mut self.profile);
+
+		Ok(())
+	}
+
+	/// Returns whether the profile was written
+	fn write(&self, force: bool) -> Result<bool> {
+		if (force && self.configured) || self.profile != self.current {
+			sysfs_write(&self.path, &self.profile)?;
+			return Ok(true);
+		}
+
+		Ok(false)
+	}
+}
+impl Display for PlatformProfileInfo {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match &self.name {
+			Some(name) => write!(f, "Platform profile handler \"{name}\"")?,
+			None => write!(f, "Cooling profile")?,
+		}
+		write!(
+			f,
+			" \"{}\" (available: {})",
+			self.profile,
+			self.choices.join(", ")
+		)
+	}
+}
 
 #[derive(Debug, Clone)]
-pub struct CoolingProfileInfo(String);
+pub struct CoolingProfileInfo {
+	pub legacy: Option<PlatformProfileInfo>,
+	/// Every handler in `class/platform-profile`, empty on older kernels
+	pub handlers: Vec<PlatformProfileInfo>,
+}
 impl CoolingProfileInfo {
 	pub fn read() -> Result<Self> {
-		Ok(Self(
-			sysfs_read(Path::new("firmware/acpi/platform_profile"))
-				.unwrap_or_else(|_| "unknown".to_string()),
-		))
+		let root = Path::new(CLASS_ROOT);
+		let handlers = if sysfs_exists(root)? {
+			sysfs_list(root)?
+				.into_iter()
+				.filter(|x| x.starts_with("platform-profile-"))
+				.map(|x| PlatformProfileInfo::read_handler(root.join(x)))
+				.collect::<Result<_>>()?
+		} else {
+			Vec::new()
+		};
+
+		Ok(Self {
+			legacy: PlatformProfileInfo::read_legacy()?,
+			handlers,
+		})
 	}
 
 	pub fn write(&self) -> Result<()> {
-		if &self.0 != "unknown" {
-			sysfs_write(Path::new("firmware/acpi/platform_profile"), &self.0)
-		} else {
-			Ok(())
+		// writing the legacy interface changes every handler, so configured ones are rewritten after it
+		let mut changed = false;
+		if let Some(legacy) = &self.legacy {
+			changed = legacy.write(false)?;
+		}
+
+		for handler in &self.handlers {
+			handler.write(changed)?;
 		}
+
+		Ok(())
 	}
 }
 impl Display for CoolingProfileInfo {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		write!(f, "Cooling profile \"{}\"", self.0)
+		match &self.legacy {
+			Some(legacy) => write!(f, "{legacy}")?,
+			None => write!(f, "Cooling profile \"unknown\"")?,
+		}
+		for handler in &self.handlers {
+			write!(f, "\n{handler}")?;
+		}
+
+		Ok(())
 	}
 }
 
+/// Profile of the legacy `firmware/acpi/platform_profile` interface
 #[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct CoolingProfileConfig(String);
 impl CoolingProfileConfig {
 	pub fn apply(&self, info: &mut CoolingProfileInfo) -> Result<()> {
-		info.0.clone_from(&self.0);
+		match &mut info.legacy {
+			Some(legacy) => legacy.set(&self.0)?,
+			None if self.0 != "unknown" => {
+				warn!(
+					"platform_profile is unavailable, ignoring cooling profile {:?}",
+					self.0
+				);
+			}
+			None => {}
+		}
+
 		Ok(())
 	}
 }
 impl From<CoolingProfileInfo> for CoolingProfileConfig {
 	fn from(value: CoolingProfileInfo) -> Self {
-		Self(value.0)
+		Self(
+			value
+				.legacy
+				.map(|x| x.profile)
+				.unwrap_or_else(|| "unknown".to_string()),
+		)
+	}
+}
+
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct PlatformProfileConfig {
+	/// `name` of the handler in `class/platform-profile`
+	pub name: String,
+	pub profile: String,
+}
+impl PlatformProfileConfig {
+	pub fn apply(&self, info: &mut CoolingProfileInfo) -> Result<()> {
+		let mut found = false;
+
+		for handler in info
+			.handlers
+			.iter_mut()
+			.filter(|x| x.name.as_ref() == Some(&self.name))
+		{
+			handler.set(&self.profile)?;
+			handler.configured = true;
+			found = true;
+		}
+
+		if !found {
+			bail!("failed to find platform profile handler {:?}", self.name);
+		}
+
+		Ok(())
+	}
+}
+impl From<PlatformProfileInfo> for PlatformProfileConfig {
+	fn from(value: PlatformProfileInfo) -> Self {
+		Self {
+			name: value.name.unwrap_or_default(),
+			profile: value.profile,
+		}
 	}
 }
@@ -0,0 +1,95 @@
+use std::path::Path;
+
+use anyhow::{Result, bail};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::sysfs::{sysfs_exists, sysfs_read, sysfs_read_cpulist, sysfs_write};
+
+#[derive(Clone, Debug)]
+pub struct CpuHotplugInfo {
+	pub present: Vec<usize>,
+	online: Vec<usize>,
+	smt_active: bool,
+
+	pub offline: Vec<usize>,
+}
+impl CpuHotplugInfo {
+	pub fn read() -> Result<Self> {
+		let present = sysfs_read_cpulist(Path::new("devices/system/cpu/present"))?;
+		let online = sysfs_read_cpulist(Path::new("devices/system/cpu/online"))?;
+		let smt_active = Path::new("devices/system/cpu/smt/active");
+		let smt_active = !sysfs_exists(smt_active)? || sysfs_read::<usize>(smt_active)? == 1;
+
+		Ok(Self {
+			offline: present
+				.iter()
+				.copied()
+				.filter(|x| !online.contains(x))
+				.collect(),
+			present,
+			online,
+			smt_active,
+		})
+	}
+
+	/// Returns whether any cpu changed state
+	pub fn write(&self) -> Result<bool> {
+		let mut changed = false;
+
+		// online first so that we never end up with fewer cpus than either profile wants
+		for id in &self.present {
+			if !self.offline.contains(id) && !self.online.contains(id) {
+				if let Err(err) =
+					sysfs_write(Path::new(&format!("devices/system/cpu/cpu{id}/online")), 1)
+				{
+					// the kernel refuses to online smt siblings while smt is disabled, and their
+					// topology is gone so they can't be told apart from other offline cpus
+					if self.smt_active {
+						return Err(err);
+					}
+					debug!("failed to online cpu {id} with smt disabled, skipping: {err:?}");
+					continue;
+				}
+				changed = true;
+			}
+		}
+		for id in &self.offline {
+			if self.online.contains(id) {
+				sysfs_write(Path::new(&format!("devices/system/cpu/cpu{id}/online")), 0)?;
+				changed = true;
+			}
+		}
+
+		Ok(changed)
+	}
+}
+
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct CpuHotplugConfig {
+	/// CPUs to take offline, every other present CPU is brought online
+	pub offline: Vec<usize>,
+}
+impl CpuHotplugConfig {
+	pub fn apply(&self, info: &mut CpuHotplugInfo) -> Result<()> {
+		for id in &self.offline {
+			if *id == 0 {
+				bail!("cpu 0 can't be taken offline");
+			}
+			if !info.present.contains(id) {
+				bail!("failed to find cpu with id {id}");
+			}
+		}
+
+		info.offline.clone_from(&self.offline);
+
+		Ok(())
+	}
+}
+impl From<CpuHotplugInfo> for CpuHotplugConfig {
+	fn from(value: CpuHotplugInfo) -> Self {
+		Self {
+			offline: value.offline,
+		}
+	}
+}
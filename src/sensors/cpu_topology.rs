@@ -0,0 +1,209 @@
+use std::{fmt::Display, path::Path, str::FromStr, sync::Mutex};
+
+use anyhow::{Context, Result, anyhow, bail};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::sysfs::{sysfs_exists, sysfs_read, sysfs_read_cpulist};
+
+/// `(physical_package_id, core_id)` of every cpu seen online, the kernel removes `topology/` while
+/// a cpu is offline
+static CORE_IDS: Mutex<Vec<(usize, (u64, u64))>> = Mutex::new(Vec::new());
+
+#[derive(Clone, Debug)]
+pub struct CpuTopology {
+	pub present: Vec<usize>,
+	/// Empty on non-hybrid CPUs
+	pub pcores: Vec<usize>,
+	/// Empty on non-hybrid CPUs
+	pub ecores: Vec<usize>,
+	/// Every present logical CPU that isn't the first thread of its core, offline CPUs powerd never
+	/// saw online are left out
+	pub smt_siblings: Vec<usize>,
+}
+impl CpuTopology {
+	pub fn read() -> Result<Self> {
+		let present = sysfs_read_cpulist(Path::new("devices/system/cpu/present"))?;
+
+		let read_cores = |path: &Path| -> Result<Vec<usize>> {
+			if sysfs_exists(path)? {
+				sysfs_read_cpulist(path)
+			} else {
+				Ok(Vec::new())
+			}
+		};
+
+		// thread_siblings_list only lists online cpus, so build the cores from the ids of each cpu
+		// instead, otherwise the selector would change with hotplug
+		let mut core_ids = CORE_IDS.lock().unwrap();
+		for id in &present {
+			let root = format!("devices/system/cpu/cpu{id}/topology");
+			if !sysfs_exists(Path::new(&root))? {
+				continue;
+			}
+			let core = (
+				sysfs_read(Path::new(&format!("{root}/physical_package_id")))?,
+				sysfs_read(Path::new(&format!("{root}/core_id")))?,
+			);
+			if let Some(x) = core_ids.iter_mut().find(|(x, _)| x == id) {
+				x.1 = core;
+			} else {
+				core_ids.push((*id, core));
+			}
+		}
+
+		let mut smt_siblings = Vec::new();
+		for id in &present {
+			let Some((_, core)) = core_ids.iter().find(|(x, _)| x == id) else {
+				debug!("cpu {id} was never online, leaving it out of smt-siblings");
+				continue;
+			};
+			if core_ids
+				.iter()
+				.any(|(x, y)| x < id && y == core && present.contains(x))
+			{
+				smt_siblings.push(*id);
+			}
+		}
+		drop(core_ids);
+
+		Ok(Self {
+			present,
+			pcores: read_cores(Path::new("devices/cpu_core/cpus"))?,
+			ecores: read_cores(Path::new("devices/cpu_atom/cpus"))?,
+			smt_siblings,
+		})
+	}
+
+	pub fn hybrid(&self) -> bool {
+		!self.pcores.is_empty() || !self.ecores.is_empty()
+	}
+
+	/// Resolves selectors into a sorted list of cpu ids
+	pub fn resolve(&self, selectors: &[CpuSelector]) -> Vec<usize> {
+		let mut ids = Vec::new();
+
+		for selector in selectors {
+			match selector {
+				CpuSelector::Id(id) => ids.push(*id),
+				CpuSelector::Range(start, end) => ids.extend(*start..=*end),
+				CpuSelector::All => ids.extend_from_slice(&self.present),
+				// every core is a p-core on non-hybrid CPUs
+				CpuSelector::PCores if !self.hybrid() => ids.extend_from_slice(&self.present),
+				CpuSelector::PCores => ids.extend_from_slice(&self.pcores),
+				CpuSelector::ECores => ids.extend_from_slice(&self.ecores),
+				CpuSelector::SmtSiblings => ids.extend_from_slice(&self.smt_siblings),
+			}
+		}
+
+		ids.sort_unstable();
+		ids.dedup();
+
+		ids
+	}
+
+	/// Picks the shortest selectors that resolve to exactly `ids`
+	pub fn selectors(&self, ids: &[usize]) -> Vec<CpuSelector> {
+		let mut ids = ids.to_vec();
+		ids.sort_unstable();
+		ids.dedup();
+
+		for selector in [CpuSelector::All, CpuSelector::PCores, CpuSelector::ECores] {
+			let resolved = self.resolve(std::slice::from_ref(&selector));
+			if !resolved.is_empty() && resolved == ids {
+				return vec![selector];
+			}
+		}
+
+		let mut selectors = Vec::new();
+		for id in ids {
+			match selectors.last_mut() {
+				Some(CpuSelector::Range(_, end)) if *end + 1 == id => *end = id,
+				Some(last @ &mut CpuSelector::Id(start)) if start + 1 == id => {
+					*last = CpuSelector::Range(start, id);
+				}
+				_ => selectors.push(CpuSelector::Id(id)),
+			}
+		}
+
+		selectors
+	}
+}
+
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum CpuSelectorRepr {
+	Id(usize),
+	Named(String),
+}
+
+/// A logical cpu id, a range like `"0-7"`, or one of `"all"`, `"pcores"`, `"ecores"` and
+/// `"smt-siblings"`
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "CpuSelectorRepr", into = "CpuSelectorRepr")]
+pub enum CpuSelector {
+	Id(usize),
+	Range(usize, usize),
+	All,
+	PCores,
+	ECores,
+	SmtSiblings,
+}
+impl FromStr for CpuSelector {
+	type Err = anyhow::Error;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"all" => Ok(Self::All),
+			"pcores" => Ok(Self::PCores),
+			"ecores" => Ok(Self::ECores),
+			"smt-siblings" => Ok(Self::SmtSiblings),
+			x => {
+				if let Some((start, end)) = x.split_once('-') {
+					let start = start
+						.parse()
+						.with_context(|| format!("invalid cpu range {x:?}"))?;
+					let end = end
+						.parse()
+						.with_context(|| format!("invalid cpu range {x:?}"))?;
+					if start > end {
+						bail!("invalid cpu range {x:?}");
+					}
+					Ok(Self::Range(start, end))
+				} else {
+					x.parse()
+						.map(Self::Id)
+						.map_err(|_| anyhow!("invalid cpu selector: {x}"))
+				}
+			}
+		}
+	}
+}
+impl Display for CpuSelector {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Id(id) => write!(f, "{id}"),
+			Self::Range(start, end) => write!(f, "{start}-{end}"),
+			Self::All => write!(f, "all"),
+			Self::PCores => write!(f, "pcores"),
+			Self::ECores => write!(f, "ecores"),
+			Self::SmtSiblings => write!(f, "smt-siblings"),
+		}
+	}
+}
+impl TryFrom<CpuSelectorRepr> for CpuSelector {
+	type Error = anyhow::Error;
+	fn try_from(value: CpuSelectorRepr) -> Result<Self, Self::Error> {
+		match value {
+			CpuSelectorRepr::Id(id) => Ok(Self::Id(id)),
+			CpuSelectorRepr::Named(name) => name.parse(),
+		}
+	}
+}
+impl From<CpuSelector> for CpuSelectorRepr {
+	fn from(value: CpuSelector) -> Self {
+		match value {
+			CpuSelector::Id(id) => Self::Id(id),
+			x => Self::Named(x.to_string()),
+		}
+	}
+}
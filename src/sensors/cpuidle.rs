@@ -0,0 +1,275 @@
+use std::{
+	fmt::Display,
+	path::{Path, PathBuf},
+	time::Duration,
+};
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	msr::{Msr, msr_get_bits, msr_read, msr_set_bits, msr_write},
+	sysfs::{sysfs_exists, sysfs_read, sysfs_read_cpulist, sysfs_write},
+};
+
+/// Package C-state limit field of `MSR_PKG_CST_CONFIG_CONTROL`
+const PKG_CST_LIMIT_MAX: u64 = 0xF;
+
+#[derive(Clone, Debug)]
+pub struct CpuidleStateInfo {
+	pub id: usize,
+	pub name: String,
+	pub residency: Duration,
+
+	pub disabled: bool,
+}
+impl CpuidleStateInfo {
+	fn read(cpuidle: &Path, id: usize) -> Result<Option<Self>> {
+		let root = cpuidle.join(format!("state{id}"));
+
+		if !sysfs_exists(&root)? {
+			return Ok(None);
+		}
+
+		Ok(Some(Self {
+			id,
+			name: sysfs_read(&root.join("name"))?,
+			residency: Duration::from_micros(sysfs_read(&root.join("time"))?),
+
+			disabled: sysfs_read::<usize>(&root.join("disable"))? == 1,
+		}))
+	}
+
+	fn write(&self, cpuidle: &Path) -> Result<()> {
+		sysfs_write(
+			&cpuidle.join(format!("state{}/disable", self.id)),
+			if self.disabled { 1 } else { 0 },
+		)
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct CpuidleCpuInfo {
+	pub id: usize,
+	pub states: Vec<CpuidleStateInfo>,
+}
+impl CpuidleCpuInfo {
+	fn read(id: usize) -> Result<Option<Self>> {
+		let root = PathBuf::from(format!("devices/system/cpu/cpu{id}/cpuidle"));
+
+		if !sysfs_exists(&root)? {
+			return Ok(None);
+		}
+
+		let mut states = Vec::new();
+		while let Some(state) = CpuidleStateInfo::read(&root, states.len())? {
+			states.push(state);
+		}
+
+		Ok(Some(Self { id, states }))
+	}
+
+	fn write(&self) -> Result<()> {
+		let root = PathBuf::from(format!("devices/system/cpu/cpu{}/cpuidle", self.id));
+
+		for state in &self.states {
+			state.write(&root)?;
+		}
+
+		Ok(())
+	}
+}
+impl Display for CpuidleCpuInfo {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let total: Duration = self.states.iter().map(|x| x.residency).sum();
+
+		write!(f, "CPU {}:", self.id)?;
+		for state in &self.states {
+			write!(
+				f,
+				" {}{} {:.1}%",
+				state.name,
+				if state.disabled { " (disabled)" } else { "" },
+				if total.is_zero() {
+					0.0
+				} else {
+					state.residency.as_secs_f64() / total.as_secs_f64() * 100.0
+				}
+			)?;
+		}
+
+		Ok(())
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct CpuidleInfo {
+	pub available_governors: Vec<String>,
+	pub cpus: Vec<CpuidleCpuInfo>,
+
+	pub governor: Option<String>,
+	pub pkg_cst_limit: Option<u64>,
+}
+impl CpuidleInfo {
+	pub fn read() -> Result<Self> {
+		let root = Path::new("devices/system/cpu/cpuidle");
+
+		let mut cpus = Vec::new();
+		for id in sysfs_read_cpulist(Path::new("devices/system/cpu/online"))? {
+			if let Some(cpu) = CpuidleCpuInfo::read(id)? {
+				cpus.push(cpu);
+			}
+		}
+
+		let governor = if sysfs_exists(&root.join("current_governor"))? {
+			Some(sysfs_read(&root.join("current_governor"))?)
+		} else {
+			None
+		};
+		let available_governors = if sysfs_exists(&root.join("available_governors"))? {
+			sysfs_read::<String>(&root.join("available_governors"))?
+				.split_whitespace()
+				.map(ToOwned::to_owned)
+				.collect()
+		} else {
+			Vec::new()
+		};
+
+		Ok(Self {
+			available_governors,
+			governor,
+			pkg_cst_limit: msr_read(0, Msr::PkgCstConfigControl)
+				.ok()
+				.map(|x| msr_get_bits(x, 0..=3)),
+			cpus,
+		})
+	}
+
+	pub fn write(&self) -> Result<()> {
+		if let Some(governor) = &self.governor {
+			sysfs_write(
+				Path::new("devices/system/cpu/cpuidle/current_governor"),
+				governor,
+			)?;
+		}
+
+		for cpu in &self.cpus {
+			cpu.write()?;
+
+			if let Some(limit) = self.pkg_cst_limit {
+				let cst = msr_read(cpu.id, Msr::PkgCstConfigControl)?;
+				msr_write(
+					cpu.id,
+					Msr::PkgCstConfigControl,
+					msr_set_bits(cst, 0..=3, limit),
+				)?;
+			}
+		}
+
+		Ok(())
+	}
+}
+impl Display for CpuidleInfo {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "cpuidle: ")?;
+		if let Some(governor) = &self.governor {
+			write!(
+				f,
+				"\"{governor}\" governor (available: {:?})",
+				self.available_governors
+			)?;
+		} else {
+			write!(f, "unknown governor")?;
+		}
+		if let Some(limit) = self.pkg_cst_limit {
+			writeln!(f, ", package C-state limit {limit}")?;
+		} else {
+			writeln!(f, ", package C-state limit unknown")?;
+		}
+
+		for cpu in &self.cpus {
+			writeln!(f, "{cpu}")?;
+		}
+
+		Ok(())
+	}
+}
+
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct CpuidleStateConfig {
+	pub name: String,
+	pub disabled: bool,
+}
+impl CpuidleStateConfig {
+	pub fn apply(&self, cpus: &mut [CpuidleCpuInfo]) -> Result<()> {
+		let mut found = false;
+
+		for state in cpus
+			.iter_mut()
+			.flat_map(|x| x.states.iter_mut())
+			.filter(|x| x.name == self.name)
+		{
+			state.disabled = self.disabled;
+			found = true;
+		}
+
+		if !found {
+			bail!("failed to find cpuidle state with name {}", self.name);
+		}
+
+		Ok(())
+	}
+}
+
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct CpuidleConfig {
+	pub governor: Option<String>,
+	pub states: Vec<CpuidleStateConfig>,
+	pub pkg_cst_limit: Option<u64>,
+}
+impl CpuidleConfig {
+	pub fn apply(&self, info: &mut CpuidleInfo) -> Result<()> {
+		if let Some(governor) = &self.governor {
+			if !info.available_governors.is_empty() && !info.available_governors.contains(governor)
+			{
+				bail!(
+					"invalid cpuidle governor {governor:?}, available: {:?}",
+					info.available_governors
+				);
+			}
+			info.governor = Some(governor.clone());
+		}
+
+		for state in &self.states {
+			state.apply(&mut info.cpus)?;
+		}
+
+		if let Some(limit) = self.pkg_cst_limit {
+			if limit > PKG_CST_LIMIT_MAX {
+				bail!("invalid package c-state limit {limit}, expected 0-{PKG_CST_LIMIT_MAX}");
+			}
+			info.pkg_cst_limit = Some(limit);
+		}
+
+		Ok(())
+	}
+}
+impl From<CpuidleInfo> for CpuidleConfig {
+	fn from(value: CpuidleInfo) -> Self {
+		let mut states: Vec<CpuidleStateConfig> = Vec::new();
+		for state in value.cpus.into_iter().flat_map(|x| x.states) {
+			if !states.iter().any(|x| x.name == state.name) {
+				states.push(CpuidleStateConfig {
+					name: state.name,
+					disabled: state.disabled,
+				});
+			}
+		}
+
+		Self {
+			governor: value.governor,
+			states,
+			pkg_cst_limit: value.pkg_cst_limit,
+		}
+	}
+}
@@ -0,0 +1,254 @@
+use std::fmt::{Display, Write};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{lzma::lzma_alone_decompress, sensors::intel_dptf::DptfInfo};
+
+const GDDV_SIGNATURE: u16 = 0x5555;
+const GDDV_FLAG_COMPRESSED: u32 = 1;
+
+/// Policy UUIDs known to the int3400 driver, in the order of `int3400_thermal_uuids`
+const DPTF_POLICIES: &[(&str, &str)] = &[
+	("passive", "42A441D6-AE6A-462b-A84B-4A8CE79027D3"),
+	("active", "3A95C389-E4B8-4629-A526-C52C88626BAE"),
+	("critical", "97C68AE7-15FA-499c-B8C9-5DA81D606E0A"),
+	("adaptive", "63BE270F-1C11-48FD-A6F7-3AF253FF3E2D"),
+	("emergency-call", "5349962F-71E6-431D-9AE8-0A635B710AEE"),
+	("passive-2", "9E04115A-AE87-4D1C-9500-0F3E340BFE75"),
+	("power-boss", "F5A35014-C209-46A4-993A-EB56DE7530A1"),
+	("virtual-sensor", "6ED722A7-9240-48A5-B479-31EEF723D7CF"),
+	("cooling-mode", "16CAF1B7-DD38-40ED-B1C1-1B8A1913D531"),
+	(
+		"hardware-duty-cycling",
+		"BE84BABF-C4D4-403D-B495-3128FD44dAC1",
+	),
+];
+
+pub fn policy_name(uuid: &str) -> Option<&'static str> {
+	DPTF_POLICIES
+		.iter()
+		.find(|(_, x)| x.eq_ignore_ascii_case(uuid))
+		.map(|(name, _)| *name)
+}
+
+pub fn policy_uuid(name: &str) -> Option<&'static str> {
+	DPTF_POLICIES
+		.iter()
+		.find(|(x, _)| *x == name)
+		.map(|(_, uuid)| *uuid)
+}
+
+#[derive(Clone, Debug)]
+pub struct GddvEntry {
+	pub key: String,
+	pub value: Vec<u8>,
+}
+impl Display for GddvEntry {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let text = self.value.strip_suffix(&[0]).unwrap_or(&self.value);
+		match std::str::from_utf8(text) {
+			Ok(text) if text.chars().all(|x| !x.is_control() || x.is_whitespace()) => {
+				write!(f, "{}: {text:?}", self.key)
+			}
+			_ => write!(f, "{}: {} byte table", self.key, self.value.len()),
+		}
+	}
+}
+
+/// The GDDV (DPTF data vault) the firmware hands to userspace through the int3400 device
+#[derive(Clone, Debug)]
+pub struct Gddv {
+	pub version: u32,
+	pub compressed: bool,
+	pub entries: Vec<GddvEntry>,
+}
+impl Gddv {
+	pub fn parse(buf: &[u8]) -> Result<Self> {
+		let mut reader = Reader { buf, offset: 0 };
+
+		let signature = reader.u16()?;
+		if signature != GDDV_SIGNATURE {
+			bail!("invalid data vault signature {signature:#x}");
+		}
+		let header_size = reader.u16()? as usize;
+		let version = reader.u32()?;
+		let flags = reader.u32()?;
+
+		// the compressed payload is a whole vault again, with its own header
+		if flags & GDDV_FLAG_COMPRESSED != 0 {
+			let payload = buf
+				.get(header_size..)
+				.context("data vault header truncated")?;
+			let inner =
+				lzma_alone_decompress(payload).context("failed to decompress data vault")?;
+			return Ok(Self {
+				compressed: true,
+				..Self::parse(&inner)?
+			});
+		}
+
+		reader.offset = header_size;
+		let mut entries = Vec::new();
+		while reader.offset < buf.len() {
+			let _key_flags = reader.u32()?;
+			let key_len = reader.u32()? as usize;
+			let key = reader.bytes(key_len)?;
+			let _value_flags = reader.u32()?;
+			let value_len = reader.u32()? as usize;
+			let value = reader.bytes(value_len)?;
+
+			entries.push(GddvEntry {
+				key: String::from_utf8_lossy(key.strip_suffix(&[0]).unwrap_or(key)).into_owned(),
+				value: value.to_vec(),
+			});
+		}
+
+		Ok(Self {
+			version,
+			compressed: false,
+			entries,
+		})
+	}
+}
+
+struct Reader<'a> {
+	buf: &'a [u8],
+	offset: usize,
+}
+impl<'a> Reader<'a> {
+	fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+		let bytes = self
+			.buf
+			.get(self.offset..self.offset + len)
+			.with_context(|| format!("data vault truncated at offset {}", self.offset))?;
+		self.offset += len;
+		Ok(bytes)
+	}
+	fn u16(&mut self) -> Result<u16> {
+		Ok(u16::from_le_bytes(self.bytes(2)?.try_into()?))
+	}
+	fn u32(&mut self) -> Result<u32> {
+		Ok(u32::from_le_bytes(self.bytes(4)?.try_into()?))
+	}
+}
+
+pub fn dptf_dump() -> Result<String> {
+	let info = DptfInfo::read()?;
+	let policy = info
+		.policy
+		.as_ref()
+		.context("failed to find a dptf device")?;
+	let mut out = String::new();
+
+	writeln!(out, "DPTF policies:")?;
+	for uuid in &policy.uuids {
+		write!(out, "{uuid} {}", policy_name(uuid).unwrap_or("unknown"))?;
+		if *uuid == policy.uuid {
+			write!(out, " (current)")?;
+		}
+		writeln!(out)?;
+	}
+
+	match info.read_data_vault()? {
+		Some(vault) => {
+			let gddv = Gddv::parse(&vault).context("failed to parse data vault")?;
+			write!(out, "\nData vault version {}", gddv.version)?;
+			if gddv.compressed {
+				write!(out, " (compressed)")?;
+			}
+			for entry in &gddv.entries {
+				write!(out, "\n{entry}")?;
+			}
+		}
+		None => write!(out, "\nNo data vault")?,
+	}
+
+	Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{GDDV_FLAG_COMPRESSED, GDDV_SIGNATURE, Gddv, policy_name, policy_uuid};
+	use crate::lzma::tests::VAULT_LZMA;
+
+	fn header(flags: u32) -> Vec<u8> {
+		let mut buf = Vec::new();
+		buf.extend_from_slice(&GDDV_SIGNATURE.to_le_bytes());
+		buf.extend_from_slice(&12u16.to_le_bytes());
+		buf.extend_from_slice(&2u32.to_le_bytes());
+		buf.extend_from_slice(&flags.to_le_bytes());
+		buf
+	}
+
+	fn vault(entries: &[(&str, &[u8])]) -> Vec<u8> {
+		let mut buf = header(0);
+		for (key, value) in entries {
+			let key = [key.as_bytes(), &[0]].concat();
+			buf.extend_from_slice(&0u32.to_le_bytes());
+			buf.extend_from_slice(&(key.len() as u32).to_le_bytes());
+			buf.extend_from_slice(&key);
+			buf.extend_from_slice(&0u32.to_le_bytes());
+			buf.extend_from_slice(&(value.len() as u32).to_le_bytes());
+			buf.extend_from_slice(value);
+		}
+		buf
+	}
+
+	fn check_entries(gddv: &Gddv) {
+		assert_eq!(gddv.version, 2);
+		assert_eq!(gddv.entries.len(), 2);
+		assert_eq!(gddv.entries[0].key, "/participants/IETM.D0/_ppd");
+		assert_eq!(gddv.entries[0].value, b"ppd\0");
+		assert_eq!(
+			gddv.entries[0].to_string(),
+			"/participants/IETM.D0/_ppd: \"ppd\""
+		);
+		assert_eq!(gddv.entries[1].key, "/shared/tables/art");
+		assert_eq!(gddv.entries[1].value, [0, 1, 2, 3, 4, 5, 6, 7].repeat(2));
+		assert_eq!(
+			gddv.entries[1].to_string(),
+			"/shared/tables/art: 16 byte table"
+		);
+	}
+
+	#[test]
+	fn uncompressed() {
+		let art = [0, 1, 2, 3, 4, 5, 6, 7].repeat(2);
+		let gddv = Gddv::parse(&vault(&[
+			("/participants/IETM.D0/_ppd", b"ppd\0"),
+			("/shared/tables/art", &art),
+		]))
+		.unwrap();
+		assert!(!gddv.compressed);
+		check_entries(&gddv);
+	}
+
+	#[test]
+	fn compressed() {
+		let buf = [header(GDDV_FLAG_COMPRESSED).as_slice(), VAULT_LZMA].concat();
+		let gddv = Gddv::parse(&buf).unwrap();
+		assert!(gddv.compressed);
+		check_entries(&gddv);
+	}
+
+	#[test]
+	fn invalid() {
+		let mut buf = vault(&[("/shared/tables/art", &[1, 2, 3])]);
+		assert!(Gddv::parse(&buf[..buf.len() - 1]).is_err());
+		buf[0] = 0;
+		assert!(Gddv::parse(&buf).is_err());
+	}
+
+	#[test]
+	fn policies() {
+		assert_eq!(
+			policy_uuid("adaptive"),
+			Some("63BE270F-1C11-48FD-A6F7-3AF253FF3E2D")
+		);
+		assert_eq!(
+			policy_name("42a441d6-ae6a-462b-a84b-4a8ce79027d3"),
+			Some("passive")
+		);
+		assert_eq!(policy_uuid("unknown"), None);
+	}
+}
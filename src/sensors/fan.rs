@@ -0,0 +1,545 @@
+use std::{
+	fmt::Display,
+	path::{Path, PathBuf},
+	sync::Mutex,
+};
+
+use anyhow::{Context, Result, bail};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::sysfs::{sysfs_exists, sysfs_list, sysfs_read, sysfs_read_opt, sysfs_write};
+
+const HWMON_ROOT: &str = "class/hwmon";
+/// thinkpad_acpi only takes fan levels through procfs, and only with `fan_control=1`
+const THINKPAD_FAN: &str = "/proc/acpi/ibm/fan";
+const THINKPAD_LEVELS: &[&str] = &[
+	"auto",
+	"full-speed",
+	"disengaged",
+	"0",
+	"1",
+	"2",
+	"3",
+	"4",
+	"5",
+	"6",
+	"7",
+];
+
+/// `pwmN_enable` values from the hwmon ABI, anything above manual is driver specific
+const PWM_ENABLE_MANUAL: u8 = 1;
+const PWM_ENABLE_AUTO: u8 = 2;
+
+/// Drivers whose fan curves don't follow the hwmon ABI
+struct FanCurveDriver {
+	/// hwmon name
+	name: &'static str,
+	/// Auto point temperatures are in degrees instead of millidegrees
+	degrees: bool,
+	/// `pwmN_enable` value that turns the curve on
+	enable: u8,
+}
+const FAN_CURVE_DRIVERS: &[FanCurveDriver] = &[FanCurveDriver {
+	name: "asus_custom_fan_curve",
+	degrees: true,
+	enable: 1,
+}];
+
+fn fan_curve_driver(name: &str) -> Option<&'static FanCurveDriver> {
+	FAN_CURVE_DRIVERS.iter().find(|x| x.name == name)
+}
+
+/// A fan powerd took over, with the state it was in before so it can be handed back as it was
+#[derive(Clone, Debug)]
+enum FanHandle {
+	Pwm {
+		/// hwmon directory
+		path: PathBuf,
+		channel: usize,
+		/// Original `pwmN_enable`, automatic modes above 2 are driver specific
+		original: u8,
+		/// Original `pwmN`, for fans that were already in manual mode
+		original_pwm: Option<u8>,
+	},
+	Thinkpad {
+		original: String,
+	},
+}
+impl FanHandle {
+	fn same_fan(&self, other: &Self) -> bool {
+		match (self, other) {
+			(
+				Self::Pwm {
+					path: a,
+					channel: a_channel,
+					..
+				},
+				Self::Pwm {
+					path: b,
+					channel: b_channel,
+					..
+				},
+			) => a == b && a_channel == b_channel,
+			(Self::Thinkpad { .. }, Self::Thinkpad { .. }) => true,
+			_ => false,
+		}
+	}
+
+	fn restore(&self) {
+		info!("restoring original control of fan {self:?}");
+		let res = match self {
+			Self::Pwm {
+				path,
+				channel,
+				original,
+				original_pwm,
+			} => {
+				let enable = path.join(format!("pwm{channel}_enable"));
+				match original_pwm {
+					Some(pwm) if *original <= PWM_ENABLE_MANUAL => {
+						sysfs_write(&path.join(format!("pwm{channel}")), pwm)
+							.and_then(|()| sysfs_write(&enable, original))
+					}
+					_ => sysfs_write(&enable, original),
+				}
+			}
+			Self::Thinkpad { original } => {
+				std::fs::write(THINKPAD_FAN, format!("level {original}"))
+					.context("failed to write thinkpad fan")
+			}
+		};
+		if let Err(err) = res {
+			warn!("failed to restore original control of fan {self:?}: {err:?}");
+		}
+	}
+}
+
+/// Fans powerd took over from the firmware, so they can be handed back. Held for the whole fan
+/// write so a restore can't interleave with one
+static CONTROLLED_FANS: Mutex<Vec<FanHandle>> = Mutex::new(Vec::new());
+
+/// Records a fan before powerd first changes it, later calls keep the original state
+fn fan_controlled(fans: &mut Vec<FanHandle>, handle: FanHandle) {
+	if !fans.iter().any(|x| x.same_fan(&handle)) {
+		fans.push(handle);
+	}
+}
+
+/// Stops tracking a fan without restoring it, returns the handle if powerd controlled it
+fn fan_released(fans: &mut Vec<FanHandle>, handle: &FanHandle) -> Option<FanHandle> {
+	let idx = fans.iter().position(|x| x.same_fan(handle))?;
+	Some(fans.remove(idx))
+}
+
+/// Hands every fan `keep` doesn't match back in the state it was in before powerd took it over
+fn fans_restore_unless(fans: &mut Vec<FanHandle>, keep: impl Fn(&FanHandle) -> bool) {
+	fans.retain(|handle| {
+		if keep(handle) {
+			true
+		} else {
+			handle.restore();
+			false
+		}
+	});
+}
+
+/// Hands every fan powerd has changed back in the state it was in before powerd took it over
+pub fn fans_restore() {
+	fans_restore_unless(&mut CONTROLLED_FANS.lock().unwrap(), |_| false);
+}
+
+/// Like [`fans_restore`], but exits while still holding the lock so no other thread can take a
+/// fan back in between
+pub fn fans_restore_and_exit(code: i32) -> ! {
+	let mut fans = CONTROLLED_FANS.lock().unwrap();
+	fans_restore_unless(&mut fans, |_| false);
+	std::process::exit(code)
+}
+
+/// How a fan is driven, e.g. `"auto"`, `{ "fixed": 128 }`, `{ "curve": { "points": [[40, 0],
+/// [70, 255]] } }` or `{ "level": "3" }`
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FanMode {
+	Auto,
+	/// Manual PWM duty cycle, 0-255
+	Fixed(u8),
+	/// `pwmN_auto_point` curve as (degrees Celsius, PWM) pairs, with one pair per point the
+	/// driver offers
+	Curve {
+		points: Vec<(i64, u8)>,
+		/// `pwmN_enable` value that turns the curve on, which is driver specific. Only optional
+		/// for drivers powerd knows, like asus-wmi
+		#[serde(default)]
+		enable: Option<u8>,
+	},
+	/// thinkpad_acpi fan level
+	Level(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct PwmFanInfo {
+	path: PathBuf,
+	/// hwmon name
+	pub name: String,
+	/// N in `pwmN`
+	pub channel: usize,
+	pub enable: Option<u8>,
+	/// Curve-only devices like asus_custom_fan_curve have no `pwmN`
+	pub pwm: Option<u8>,
+	/// (degrees Celsius, PWM)
+	pub auto_points: Vec<(i64, u8)>,
+	/// asus-wmi uses degrees for auto point temperatures instead of the ABI's millidegrees
+	millidegrees: bool,
+	/// `pwmN_enable` value that turns the curve on, if the driver is known
+	curve_enable: Option<u8>,
+	mode: Option<FanMode>,
+}
+impl PwmFanInfo {
+	fn file(&self, suffix: &str) -> PathBuf {
+		self.path.join(format!("pwm{}{suffix}", self.channel))
+	}
+
+	fn read(path: &Path, name: &str, channel: usize) -> Result<Self> {
+		let mut auto_points = Vec::new();
+		loop {
+			let point = format!("pwm{channel}_auto_point{}", auto_points.len() + 1);
+			let temp = path.join(format!("{point}_temp"));
+			let pwm = path.join(format!("{point}_pwm"));
+			if !sysfs_exists(&temp)? || !sysfs_exists(&pwm)? {
+				break;
+			}
+			auto_points.push((sysfs_read(&temp)?, sysfs_read(&pwm)?));
+		}
+		let driver = fan_curve_driver(name);
+		let millidegrees = !driver.is_some_and(|x| x.degrees);
+		if millidegrees {
+			for (temp, _) in &mut auto_points {
+				*temp /= 1000;
+			}
+		}
+
+		Ok(Self {
+			path: path.to_path_buf(),
+			name: name.to_string(),
+			channel,
+			enable: sysfs_read_opt(&path.join(format!("pwm{channel}_enable")))?,
+			pwm: sysfs_read_opt(&path.join(format!("pwm{channel}")))?,
+			auto_points,
+			millidegrees,
+			curve_enable: driver.map(|x| x.enable),
+			mode: None,
+		})
+	}
+
+	fn read_all() -> Result<Vec<Self>> {
+		let root = Path::new(HWMON_ROOT);
+		if !sysfs_exists(root)? {
+			return Ok(Vec::new());
+		}
+
+		let mut fans = Vec::new();
+		for hwmon in sysfs_list(root)? {
+			let path = root.join(hwmon);
+			let name: String = sysfs_read(&path.join("name"))?;
+
+			let mut channels: Vec<usize> = sysfs_list(&path)?
+				.into_iter()
+				.filter_map(|x| {
+					let x = x.strip_prefix("pwm")?;
+					x.strip_suffix("_enable").unwrap_or(x).parse().ok()
+				})
+				.collect();
+			channels.sort_unstable();
+			channels.dedup();
+
+			for channel in channels {
+				fans.push(Self::read(&path, &name, channel)?);
+			}
+		}
+
+		Ok(fans)
+	}
+
+	fn set_mode(&mut self, mode: &FanMode) -> Result<()> {
+		match mode {
+			FanMode::Auto if self.enable.is_none() => {
+				bail!(
+					"fan {} pwm{} has no automatic mode",
+					self.name,
+					self.channel
+				)
+			}
+			FanMode::Auto => {}
+			FanMode::Fixed(_) if self.pwm.is_none() || self.enable.is_none() => {
+				bail!("fan {} pwm{} has no manual mode", self.name, self.channel)
+			}
+			FanMode::Fixed(_) => {}
+			FanMode::Curve { points, enable } => {
+				if self.enable.is_none() || self.auto_points.is_empty() {
+					bail!("fan {} pwm{} has no fan curve", self.name, self.channel);
+				}
+				if points.len() != self.auto_points.len() {
+					bail!(
+						"invalid fan curve for {} pwm{}, expected {} points",
+						self.name,
+						self.channel,
+						self.auto_points.len()
+					);
+				}
+				if points.windows(2).any(|x| x[0].0 > x[1].0) {
+					bail!(
+						"invalid fan curve for {} pwm{}, temperatures have to be ascending",
+						self.name,
+						self.channel
+					);
+				}
+				let enable = enable.or(self.curve_enable).with_context(|| {
+					format!(
+						"fan curve for {} pwm{} needs an \"enable\" value, see the driver's documentation",
+						self.name, self.channel
+					)
+				})?;
+				self.mode = Some(FanMode::Curve {
+					points: points.clone(),
+					enable: Some(enable),
+				});
+				return Ok(());
+			}
+			FanMode::Level(_) => bail!(
+				"fan {} pwm{} has no fan levels, only thinkpad fans do",
+				self.name,
+				self.channel
+			),
+		}
+		self.mode = Some(mode.clone());
+
+		Ok(())
+	}
+
+	fn handle(&self) -> FanHandle {
+		FanHandle::Pwm {
+			path: self.path.clone(),
+			channel: self.channel,
+			// set_mode only accepts modes other than auto on fans with pwmN_enable
+			original: self.enable.unwrap_or(PWM_ENABLE_AUTO),
+			original_pwm: self.pwm,
+		}
+	}
+
+	fn write(&self, fans: &mut Vec<FanHandle>) -> Result<()> {
+		match &self.mode {
+			None => {}
+			Some(FanMode::Auto) => match fan_released(fans, &self.handle()) {
+				// the firmware's automatic mode from before powerd took over
+				Some(handle @ FanHandle::Pwm { original, .. }) if original > PWM_ENABLE_MANUAL => {
+					handle.restore()
+				}
+				// leave driver specific automatic modes alone
+				_ if self.enable.is_some_and(|x| x > PWM_ENABLE_MANUAL) => {}
+				_ => sysfs_write(&self.file("_enable"), PWM_ENABLE_AUTO)?,
+			},
+			Some(FanMode::Fixed(pwm)) => {
+				fan_controlled(fans, self.handle());
+				sysfs_write(&self.file("_enable"), PWM_ENABLE_MANUAL)?;
+				sysfs_write(&self.file(""), pwm)?;
+			}
+			Some(FanMode::Curve { points, enable }) => {
+				let enable = enable.expect("set_mode resolves the curve enable value");
+				fan_controlled(fans, self.handle());
+				let scale = if self.millidegrees { 1000 } else { 1 };
+				for (i, (temp, pwm)) in points.iter().enumerate() {
+					let point = format!("_auto_point{}", i + 1);
+					sysfs_write(&self.file(&format!("{point}_temp")), temp * scale)?;
+					sysfs_write(&self.file(&format!("{point}_pwm")), pwm)?;
+				}
+				sysfs_write(&self.file("_enable"), enable)?;
+			}
+			Some(FanMode::Level(_)) => unreachable!("pwm fans have no levels"),
+		}
+
+		Ok(())
+	}
+}
+impl Display for PwmFanInfo {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "Fan {} pwm{}:", self.name, self.channel)?;
+		if let Some(enable) = self.enable {
+			write!(f, " enable {enable}")?;
+		}
+		if let Some(pwm) = self.pwm {
+			write!(f, " pwm {pwm}/255")?;
+		}
+		if !self.auto_points.is_empty() {
+			write!(
+				f,
+				" curve {}",
+				self.auto_points
+					.iter()
+					.map(|(temp, pwm)| format!("{temp}degC:{pwm}"))
+					.collect::<Vec<_>>()
+					.join(" ")
+			)?;
+		}
+
+		Ok(())
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct ThinkpadFanInfo {
+	pub level: String,
+	pub speed: Option<u64>,
+	mode: Option<String>,
+}
+impl ThinkpadFanInfo {
+	fn read() -> Result<Option<Self>> {
+		let Ok(status) = std::fs::read_to_string(THINKPAD_FAN) else {
+			return Ok(None);
+		};
+
+		let field = |name: &str| {
+			status
+				.lines()
+				.find_map(|x| x.strip_prefix(name)?.strip_prefix(':'))
+				.map(str::trim)
+		};
+
+		Ok(Some(Self {
+			level: field("level")
+				.context("thinkpad fan has no level")?
+				.to_string(),
+			speed: field("speed").and_then(|x| x.parse().ok()),
+			mode: None,
+		}))
+	}
+
+	fn write(&self, fans: &mut Vec<FanHandle>) -> Result<()> {
+		if let Some(level) = &self.mode {
+			let handle = FanHandle::Thinkpad {
+				original: self.level.clone(),
+			};
+			if level == "auto" {
+				fan_released(fans, &handle);
+			} else {
+				fan_controlled(fans, handle);
+			}
+			std::fs::write(THINKPAD_FAN, format!("level {level}")).context(
+				"failed to write thinkpad fan level, is thinkpad_acpi fan_control=1 set?",
+			)?;
+		}
+
+		Ok(())
+	}
+}
+impl Display for ThinkpadFanInfo {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "ThinkPad fan: level {}", self.level)?;
+		if let Some(speed) = self.speed {
+			write!(f, " at {speed}RPM")?;
+		}
+
+		Ok(())
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct FanInfo {
+	pub pwm: Vec<PwmFanInfo>,
+	pub thinkpad: Option<ThinkpadFanInfo>,
+}
+impl FanInfo {
+	pub fn read() -> Result<Self> {
+		Ok(Self {
+			pwm: PwmFanInfo::read_all()?,
+			thinkpad: ThinkpadFanInfo::read()?,
+		})
+	}
+
+	/// Writes the configured fans and hands back every other fan powerd took over, e.g. when
+	/// switching to a profile without `fans`
+	pub fn write(&self) -> Result<()> {
+		let mut fans = CONTROLLED_FANS.lock().unwrap();
+
+		for fan in &self.pwm {
+			fan.write(&mut fans)?;
+		}
+
+		if let Some(thinkpad) = &self.thinkpad {
+			thinkpad.write(&mut fans)?;
+		}
+
+		fans_restore_unless(&mut fans, |handle| match handle {
+			FanHandle::Pwm { path, channel, .. } => self
+				.pwm
+				.iter()
+				.any(|x| x.mode.is_some() && x.path == *path && x.channel == *channel),
+			FanHandle::Thinkpad { .. } => self.thinkpad.as_ref().is_some_and(|x| x.mode.is_some()),
+		});
+
+		Ok(())
+	}
+}
+impl Display for FanInfo {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "Fan control:")?;
+		for fan in &self.pwm {
+			write!(f, "\n{fan}")?;
+		}
+		if let Some(thinkpad) = &self.thinkpad {
+			write!(f, "\n{thinkpad}")?;
+		}
+
+		Ok(())
+	}
+}
+
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct FanConfig {
+	/// hwmon name, `thinkpad` for fan levels
+	pub name: String,
+	/// N in `pwmN`, every channel of the device if unset
+	#[serde(default)]
+	pub channel: Option<usize>,
+	pub mode: FanMode,
+}
+impl FanConfig {
+	pub fn apply(&self, info: &mut FanInfo) -> Result<()> {
+		if let FanMode::Level(level) = &self.mode {
+			let thinkpad = info
+				.thinkpad
+				.as_mut()
+				.filter(|_| self.name == "thinkpad")
+				.context("fan levels are only supported on thinkpad_acpi")?;
+			if !THINKPAD_LEVELS.contains(&level.as_str()) {
+				bail!(
+					"invalid thinkpad fan level {level:?}, expected one of {}",
+					THINKPAD_LEVELS.join(", ")
+				);
+			}
+			thinkpad.mode = Some(level.clone());
+			return Ok(());
+		}
+
+		let mut found = false;
+
+		for fan in info
+			.pwm
+			.iter_mut()
+			.filter(|x| x.name == self.name && self.channel.is_none_or(|id| x.channel == id))
+		{
+			fan.set_mode(&self.mode)?;
+			found = true;
+		}
+
+		if !found {
+			match self.channel {
+				Some(channel) => bail!("failed to find fan {} pwm{channel}", self.name),
+				None => bail!("failed to find fan {}", self.name),
+			}
+		}
+
+		Ok(())
+	}
+}
@@ -0,0 +1,237 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+
+/// Hardware frequencies a [`FreqLimit`] can be relative to, in the device's native unit
+pub struct FreqRefs {
+	pub min: u64,
+	pub base: Option<u64>,
+	pub efficient: Option<u64>,
+	pub max: u64,
+	/// Native units per MHz, 1000 for cpufreq's kHz and 1 for the GPU's MHz
+	pub per_mhz: u64,
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum FreqRef {
+	Min,
+	Base,
+	Efficient,
+	Max,
+}
+impl FreqRef {
+	fn resolve(self, refs: &FreqRefs) -> Result<u64> {
+		match self {
+			Self::Min => Ok(refs.min),
+			Self::Base => refs.base.context("base frequency is unavailable"),
+			Self::Efficient => refs.efficient.context("efficient frequency is unavailable"),
+			Self::Max => Ok(refs.max),
+		}
+	}
+}
+impl FromStr for FreqRef {
+	type Err = anyhow::Error;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"min" => Ok(Self::Min),
+			"base" => Ok(Self::Base),
+			"efficient" => Ok(Self::Efficient),
+			"max" => Ok(Self::Max),
+			x => Err(anyhow!("invalid frequency reference: {x}")),
+		}
+	}
+}
+impl Display for FreqRef {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Min => write!(f, "min"),
+			Self::Base => write!(f, "base"),
+			Self::Efficient => write!(f, "efficient"),
+			Self::Max => write!(f, "max"),
+		}
+	}
+}
+
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum FreqLimitRepr {
+	Absolute(u64),
+	Relative(String),
+}
+
+/// A frequency in the device's native unit, or one relative to its hardware frequencies like
+/// `"base"`, `"80%"` (of max) or `"base+400MHz"`
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "FreqLimitRepr", into = "FreqLimitRepr")]
+pub enum FreqLimit {
+	Absolute(u64),
+	Percent(u64),
+	Relative { to: FreqRef, offset_mhz: i64 },
+}
+impl FreqLimit {
+	/// Resolves the limit, relative limits are clamped to the hardware range
+	pub fn resolve(&self, refs: &FreqRefs) -> Result<u64> {
+		let freq = match self {
+			Self::Absolute(freq) => return Ok(*freq),
+			Self::Percent(pct) => refs.max * pct / 100,
+			Self::Relative { to, offset_mhz } => {
+				let offset = offset_mhz
+					.checked_mul(refs.per_mhz as i64)
+					.with_context(|| format!("invalid frequency offset {offset_mhz}MHz"))?;
+				to.resolve(refs)?.saturating_add_signed(offset)
+			}
+		};
+
+		Ok(freq.clamp(refs.min, refs.max))
+	}
+}
+impl FromStr for FreqLimit {
+	type Err = anyhow::Error;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.trim();
+
+		if let Some(pct) = s.strip_suffix('%') {
+			let pct = pct
+				.trim()
+				.parse()
+				.with_context(|| format!("invalid frequency percentage {s:?}"))?;
+			if pct > 100 {
+				bail!("invalid frequency percentage {s:?}, expected at most 100%");
+			}
+			return Ok(Self::Percent(pct));
+		}
+
+		let (to, offset_mhz) = if let Some((to, offset)) = s.split_once('+') {
+			(to, i64::from(parse_mhz(offset)?))
+		} else if let Some((to, offset)) = s.split_once('-') {
+			(to, -i64::from(parse_mhz(offset)?))
+		} else {
+			(s, 0)
+		};
+
+		Ok(Self::Relative {
+			to: to.trim().parse()?,
+			offset_mhz,
+		})
+	}
+}
+impl Display for FreqLimit {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Absolute(freq) => write!(f, "{freq}"),
+			Self::Percent(pct) => write!(f, "{pct}%"),
+			Self::Relative { to, offset_mhz: 0 } => write!(f, "{to}"),
+			Self::Relative { to, offset_mhz } => write!(f, "{to}{offset_mhz:+}MHz"),
+		}
+	}
+}
+impl TryFrom<FreqLimitRepr> for FreqLimit {
+	type Error = anyhow::Error;
+	fn try_from(value: FreqLimitRepr) -> Result<Self, Self::Error> {
+		match value {
+			FreqLimitRepr::Absolute(freq) => Ok(Self::Absolute(freq)),
+			FreqLimitRepr::Relative(freq) => freq.parse(),
+		}
+	}
+}
+impl From<FreqLimit> for FreqLimitRepr {
+	fn from(value: FreqLimit) -> Self {
+		match value {
+			FreqLimit::Absolute(freq) => Self::Absolute(freq),
+			x => Self::Relative(x.to_string()),
+		}
+	}
+}
+
+/// Parses the magnitude of an offset, the sign comes from the separator
+fn parse_mhz(s: &str) -> Result<u32> {
+	let s = s.trim();
+	s.strip_suffix("MHz")
+		.with_context(|| format!("invalid frequency offset {s:?}, expected e.g. 400MHz"))?
+		.trim()
+		.parse()
+		.with_context(|| format!("invalid frequency offset {s:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{FreqLimit, FreqRef, FreqRefs};
+
+	fn refs() -> FreqRefs {
+		FreqRefs {
+			min: 400000,
+			base: Some(2000000),
+			efficient: None,
+			max: 4800000,
+			per_mhz: 1000,
+		}
+	}
+
+	#[test]
+	fn parse() {
+		assert_eq!("80%".parse::<FreqLimit>().unwrap(), FreqLimit::Percent(80));
+		assert_eq!(
+			"base".parse::<FreqLimit>().unwrap(),
+			FreqLimit::Relative {
+				to: FreqRef::Base,
+				offset_mhz: 0
+			}
+		);
+		assert_eq!(
+			" base + 400 MHz ".parse::<FreqLimit>().unwrap(),
+			FreqLimit::Relative {
+				to: FreqRef::Base,
+				offset_mhz: 400
+			}
+		);
+		assert_eq!(
+			"max-400MHz".parse::<FreqLimit>().unwrap(),
+			FreqLimit::Relative {
+				to: FreqRef::Max,
+				offset_mhz: -400
+			}
+		);
+	}
+
+	#[test]
+	fn parse_invalid() {
+		for s in [
+			"101%",
+			"fast",
+			"base+400",
+			"max--5MHz",
+			"max+-400MHz",
+			"max--9223372036854775808MHz",
+			"base+9999999999999999MHz",
+		] {
+			assert!(s.parse::<FreqLimit>().is_err(), "{s} parsed");
+		}
+	}
+
+	#[test]
+	fn round_trip() {
+		for s in ["80%", "min", "efficient", "base+400MHz", "max-400MHz"] {
+			assert_eq!(s.parse::<FreqLimit>().unwrap().to_string(), s);
+		}
+
+		let limit: FreqLimit = serde_json::from_str("\"base+400MHz\"").unwrap();
+		assert_eq!(serde_json::to_string(&limit).unwrap(), "\"base+400MHz\"");
+		let limit: FreqLimit = serde_json::from_str("2400000").unwrap();
+		assert_eq!(limit, FreqLimit::Absolute(2400000));
+		assert_eq!(serde_json::to_string(&limit).unwrap(), "2400000");
+	}
+
+	#[test]
+	fn resolve() {
+		let refs = refs();
+		let resolve = |s: &str| s.parse::<FreqLimit>().unwrap().resolve(&refs);
+
+		assert_eq!(resolve("50%").unwrap(), 2400000);
+		assert_eq!(resolve("base+400MHz").unwrap(), 2400000);
+		assert_eq!(resolve("min-400MHz").unwrap(), 400000);
+		assert_eq!(resolve("max+4294967295MHz").unwrap(), 4800000);
+		assert!(resolve("efficient").is_err());
+		assert_eq!(FreqLimit::Absolute(1).resolve(&refs).unwrap(), 1);
+	}
+}
@@ -0,0 +1,156 @@
+use std::{
+	fmt::Display,
+	path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use log::warn;
+
+use crate::{
+	sensors::thermal_zone::ThermalZoneInfo,
+	sysfs::{sysfs_exists, sysfs_list, sysfs_read, sysfs_read_opt},
+};
+
+const HWMON_ROOT: &str = "class/hwmon";
+
+#[derive(Clone, Debug)]
+pub struct HwmonChannel {
+	/// `<channel>_label` if the driver provides one, the channel name (e.g. `temp1`) otherwise
+	pub label: String,
+	/// Millidegrees Celsius for temperatures, RPM for fans, `None` if the sensor can't be read right now
+	pub value: Option<i64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct HwmonInfo {
+	/// Driver name like `coretemp`, `acpitz` or `thinkpad`, stable across boots unlike `hwmonN`
+	pub name: String,
+	pub temps: Vec<HwmonChannel>,
+	pub fans: Vec<HwmonChannel>,
+}
+impl HwmonInfo {
+	fn read_channels(path: &Path, files: &[String], prefix: &str) -> Result<Vec<HwmonChannel>> {
+		let mut ids: Vec<usize> = files
+			.iter()
+			.filter_map(|x| x.strip_prefix(prefix)?.strip_suffix("_input")?.parse().ok())
+			.collect();
+		ids.sort_unstable();
+
+		ids.into_iter()
+			.map(|id| {
+				let channel = format!("{prefix}{id}");
+				Ok(HwmonChannel {
+					label: sysfs_read_opt(&path.join(format!("{channel}_label")))?
+						.unwrap_or_else(|| channel.clone()),
+					// sleeping devices like nvme drives return errors
+					value: sysfs_read(&path.join(format!("{channel}_input"))).ok(),
+				})
+			})
+			.collect()
+	}
+
+	fn read(path: PathBuf) -> Result<Self> {
+		let files = sysfs_list(&path)?;
+
+		Ok(Self {
+			name: sysfs_read(&path.join("name"))?,
+			temps: Self::read_channels(&path, &files, "temp")?,
+			fans: Self::read_channels(&path, &files, "fan")?,
+		})
+	}
+
+	pub fn read_all() -> Result<Vec<Self>> {
+		let root = Path::new(HWMON_ROOT);
+		if !sysfs_exists(root)? {
+			return Ok(Vec::new());
+		}
+
+		let mut hwmon = Vec::new();
+		for name in sysfs_list(root)? {
+			// one broken driver shouldn't hide every other reading
+			match Self::read(root.join(&name)) {
+				Ok(x) => hwmon.push(x),
+				Err(err) => warn!("skipping {name}: {err:?}"),
+			}
+		}
+
+		Ok(hwmon)
+	}
+}
+
+/// A temperature or fan reading addressed by its hwmon name and label
+#[derive(Clone, Debug)]
+pub struct SensorReading {
+	/// hwmon name, or `thermal_zone` for thermal zones
+	pub name: String,
+	/// Channel label, or the type of a thermal zone
+	pub label: String,
+	pub value: i64,
+}
+
+/// Every readable temperature and fan speed, only read for `info` since reading them can wake
+/// runtime suspended devices like nvme drives
+#[derive(Clone, Debug, Default)]
+pub struct SensorReadings {
+	/// Millidegrees Celsius
+	pub temps: Vec<SensorReading>,
+	/// RPM
+	pub fans: Vec<SensorReading>,
+}
+impl SensorReadings {
+	pub fn read() -> Result<Self> {
+		let hwmon = HwmonInfo::read_all()?;
+		let zones = ThermalZoneInfo::read_all()?;
+		let mut readings = Self::default();
+
+		let flatten = |hwmon: &HwmonInfo, channels: &[HwmonChannel]| {
+			channels
+				.iter()
+				.filter_map(|x| {
+					Some(SensorReading {
+						name: hwmon.name.clone(),
+						label: x.label.clone(),
+						value: x.value?,
+					})
+				})
+				.collect::<Vec<_>>()
+		};
+		for hwmon in &hwmon {
+			readings.temps.extend(flatten(hwmon, &hwmon.temps));
+			readings.fans.extend(flatten(hwmon, &hwmon.fans));
+		}
+
+		readings.temps.extend(zones.iter().filter_map(|x| {
+			Some(SensorReading {
+				name: "thermal_zone".to_string(),
+				label: x.zone_type.clone(),
+				value: x.read_temp()?,
+			})
+		}));
+
+		Ok(readings)
+	}
+}
+impl Display for SensorReadings {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "Temperatures:")?;
+		for temp in &self.temps {
+			write!(
+				f,
+				"\n{} \"{}\": {}degC",
+				temp.name,
+				temp.label,
+				temp.value as f64 / 1000.0
+			)?;
+		}
+
+		if !self.fans.is_empty() {
+			write!(f, "\nFans:")?;
+			for fan in &self.fans {
+				write!(f, "\n{} \"{}\": {}RPM", fan.name, fan.label, fan.value)?;
+			}
+		}
+
+		Ok(())
+	}
+}
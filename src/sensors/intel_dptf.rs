@@ -1,65 +1,205 @@
 use std::{
+	collections::BTreeMap,
 	fmt::Display,
 	path::{Path, PathBuf},
+	sync::Mutex,
 };
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
+use log::debug;
 use serde::{Deserialize, Serialize};
 
-use crate::sysfs::{sysfs_exists, sysfs_read, sysfs_write};
+use crate::{
+	sensors::{
+		dptf_gddv::{policy_name, policy_uuid},
+		thermal_zone::{ThermalZoneInfo, TripPointConfig},
+	},
+	sysfs::{sysfs_exists, sysfs_list, sysfs_read, sysfs_read_bytes, sysfs_writable, sysfs_write},
+};
+
+const INT3400_DRIVER: &str = "bus/platform/drivers/int3400 thermal";
+/// The processor thermal device, which exposes the TCC offset
+const PROC_THERMAL_DRIVERS: &[&str] = &[
+	"bus/pci/drivers/proc_thermal_pci",
+	"bus/pci/drivers/proc_thermal",
+];
+const TCC_OFFSET_FILE: &str = "tcc_offset_degree_celsius";
 
-const DPTF_DEVICES: &[&str] = &[
-	"INT3400:00",
-	"INTC1040:00",
-	"INTC1041:00",
-	"INTC10A0:00",
-	"INTC1042:00",
-	"INTC1068:00",
-	"INTC10D4:00",
+/// ACPI HIDs of the DPTF manager device, more can be added with `dptf_add_hids`
+const DPTF_HIDS: &[&str] = &[
+	"INT3400", "INTC1040", "INTC1041", "INTC10A0", "INTC1042", "INTC1068", "INTC10D4",
 ];
+static EXTRA_DPTF_HIDS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+pub fn dptf_add_hids(hids: &[String]) {
+	EXTRA_DPTF_HIDS.lock().unwrap().extend_from_slice(hids);
+}
+
+fn dptf_hid_known(hid: &str) -> bool {
+	DPTF_HIDS.contains(&hid) || EXTRA_DPTF_HIDS.lock().unwrap().iter().any(|x| x == hid)
+}
+
+/// Finds the first device bound to the int3400 driver with a known HID, e.g. `INTC1041:00`
+fn find_intxx_device() -> Result<Option<PathBuf>> {
+	let driver = Path::new(INT3400_DRIVER);
+	if !sysfs_exists(driver)? {
+		return Ok(None);
+	}
+
+	for device in sysfs_list(driver)? {
+		match device.split_once(':') {
+			Some((hid, _)) if dptf_hid_known(hid) => return Ok(Some(driver.join(device))),
+			Some((hid, _)) => debug!("skipping int3400 device {device} with unknown hid {hid}"),
+			None => {}
+		}
+	}
+
+	Ok(None)
+}
+
+fn find_tcc_offset() -> Result<Option<PathBuf>> {
+	for driver in PROC_THERMAL_DRIVERS.iter().map(Path::new) {
+		if !sysfs_exists(driver)? {
+			continue;
+		}
+		for device in sysfs_list(driver)? {
+			let path = driver.join(device).join(TCC_OFFSET_FILE);
+			if sysfs_exists(&path)? {
+				return Ok(Some(path));
+			}
+		}
+	}
+
+	Ok(None)
+}
+
+/// An OEM design variable the firmware reads to pick fan and power behaviour
+#[derive(Clone, Debug)]
+pub struct OdvpInfo {
+	pub id: usize,
+	/// Mainline kernels expose ODVPs read-only
+	pub writable: bool,
+	current: u64,
+	pub value: u64,
+}
+impl OdvpInfo {
+	fn read_all(intxx_path: &Path) -> Result<Vec<Self>> {
+		let mut ids: Vec<usize> = sysfs_list(intxx_path)?
+			.into_iter()
+			.filter_map(|x| x.strip_prefix("odvp")?.parse().ok())
+			.collect();
+		ids.sort_unstable();
+
+		ids.into_iter()
+			.map(|id| {
+				let path = intxx_path.join(format!("odvp{id}"));
+				let value = sysfs_read(&path)?;
+				Ok(Self {
+					id,
+					writable: sysfs_writable(&path)?,
+					current: value,
+					value,
+				})
+			})
+			.collect()
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct DptfPolicyInfo {
+	/// Some firmware reports `INVALID` until a policy is picked
+	current: String,
+	pub uuid: String,
+	pub uuids: Vec<String>,
+}
+impl DptfPolicyInfo {
+	fn read(intxx_path: &Path) -> Result<Option<Self>> {
+		if !sysfs_exists(&intxx_path.join("uuids/current_uuid"))? {
+			return Ok(None);
+		}
+
+		let uuid: String = sysfs_read(&intxx_path.join("uuids/current_uuid"))?;
+
+		Ok(Some(Self {
+			current: uuid.clone(),
+			uuid,
+			uuids: sysfs_read::<String>(&intxx_path.join("uuids/available_uuids"))?
+				.lines()
+				.map(ToOwned::to_owned)
+				.collect(),
+		}))
+	}
+}
 
 #[derive(Clone, Debug)]
 pub struct DptfInfo {
-	intxx_path: PathBuf,
+	intxx_path: Option<PathBuf>,
+	/// `None` without an int3400 device
+	pub policy: Option<DptfPolicyInfo>,
 
-	uuid: String,
-	uuids: Vec<String>,
+	tcc_path: Option<PathBuf>,
+	/// `None` without a processor thermal device that exposes it
+	pub tcc_offset: Option<u64>,
 
-	tcc_offset: u64,
+	pub odvp: Vec<OdvpInfo>,
+	pub zones: Vec<ThermalZoneInfo>,
 }
 
 impl DptfInfo {
 	pub fn read() -> Result<Self> {
-		let intxx_base = PathBuf::from("/sys/bus/platform/drivers/int3400 thermal");
-		let intxx_path = DPTF_DEVICES
-			.iter()
-			.map(|x| intxx_base.join(x))
-			.find(|x| sysfs_exists(x).is_ok_and(|x| x))
-			.context("failed to find intxx device")?;
-
-		let uuid = sysfs_read(&intxx_path.join("uuids/current_uuid"))?;
-		let uuids = sysfs_read::<String>(&intxx_path.join("uuids/available_uuids"))?
-			.lines()
-			.map(ToOwned::to_owned)
-			.collect();
+		let intxx_path = find_intxx_device()?;
+		let tcc_path = find_tcc_offset()?;
+
+		let (policy, odvp) = match &intxx_path {
+			Some(path) => (DptfPolicyInfo::read(path)?, OdvpInfo::read_all(path)?),
+			None => (None, Vec::new()),
+		};
 
 		Ok(Self {
+			tcc_offset: tcc_path.as_deref().map(sysfs_read).transpose()?,
+			tcc_path,
 			intxx_path,
-			uuid,
-			uuids,
-			tcc_offset: sysfs_read(Path::new(
-				"bus/pci/devices/0000:00:04.0/tcc_offset_degree_celsius",
-			))?,
+			policy,
+			odvp,
+			zones: ThermalZoneInfo::read_all()?,
 		})
 	}
 
+	/// Reads the GDDV binary, which only exists if the firmware provides one
+	pub fn read_data_vault(&self) -> Result<Option<Vec<u8>>> {
+		let Some(intxx_path) = &self.intxx_path else {
+			return Ok(None);
+		};
+		let path = intxx_path.join("data_vault");
+		if !sysfs_exists(&path)? {
+			return Ok(None);
+		}
+
+		sysfs_read_bytes(&path).map(Some)
+	}
+
 	pub fn write(&self) -> Result<()> {
-		sysfs_write(&self.intxx_path.join("uuids/current_uuid"), &self.uuid)?;
+		if let Some(intxx_path) = &self.intxx_path {
+			if let Some(policy) = &self.policy
+				&& !policy.uuid.eq_ignore_ascii_case(&policy.current)
+			{
+				sysfs_write(&intxx_path.join("uuids/current_uuid"), &policy.uuid)?;
+			}
+
+			for odvp in self.odvp.iter().filter(|x| x.value != x.current) {
+				sysfs_write(&intxx_path.join(format!("odvp{}", odvp.id)), odvp.value)?;
+			}
+		}
+
+		if let Some(tcc_path) = &self.tcc_path
+			&& let Some(tcc_offset) = self.tcc_offset
+		{
+			sysfs_write(tcc_path, tcc_offset)?;
+		}
 
-		sysfs_write(
-			Path::new("bus/pci/devices/0000:00:04.0/tcc_offset_degree_celsius"),
-			self.tcc_offset,
-		)?;
+		for zone in &self.zones {
+			zone.write()?;
+		}
 
 		Ok(())
 	}
@@ -67,33 +207,136 @@ impl DptfInfo {
 
 impl Display for DptfInfo {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		writeln!(f, "DPTF:")?;
-		writeln!(f, "tcc offset: {}degC", self.tcc_offset)?;
-		writeln!(f, "available uuids: {:?}", self.uuids)?;
-		write!(f, "current uuid: {:?}", self.uuid)
+		write!(f, "DPTF:")?;
+		match self.tcc_offset {
+			Some(tcc_offset) => write!(f, "\ntcc offset: {tcc_offset}degC")?,
+			None => write!(f, "\nno tcc offset")?,
+		}
+		match &self.policy {
+			Some(policy) => {
+				write!(f, "\navailable uuids:")?;
+				for uuid in &policy.uuids {
+					write!(f, "\n{uuid} {}", policy_name(uuid).unwrap_or("unknown"))?;
+				}
+				write!(
+					f,
+					"\ncurrent uuid: {} {}",
+					policy.uuid,
+					policy_name(&policy.uuid).unwrap_or("unknown")
+				)?;
+			}
+			None => write!(f, "\nno dptf device")?,
+		}
+		for odvp in &self.odvp {
+			write!(f, "\nodvp{}: {}", odvp.id, odvp.value)?;
+			if !odvp.writable {
+				write!(f, " (read-only)")?;
+			}
+		}
+		// temperatures are part of the sensor readings
+		for zone in self.zones.iter().filter(|x| !x.trips.is_empty()) {
+			write!(f, "\n{zone}")?;
+		}
+
+		Ok(())
 	}
 }
 
 #[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct DptfConfig {
-	tcc_offset: u64,
+	#[serde(default)]
+	tcc_offset: Option<u64>,
 
-	uuid: String,
+	/// Policy UUID or its name, e.g. `"adaptive"`
+	#[serde(default)]
+	uuid: Option<String>,
+
+	/// ODVP values by index
+	#[serde(default)]
+	odvp: BTreeMap<usize, u64>,
+	#[serde(default)]
+	trip_points: Vec<TripPointConfig>,
 }
 impl DptfConfig {
 	pub fn apply(&self, info: &mut DptfInfo) -> Result<()> {
-		info.tcc_offset = self.tcc_offset;
+		if let Some(tcc_offset) = self.tcc_offset {
+			if info.tcc_offset.is_none() {
+				bail!("failed to find a tcc offset");
+			}
+			info.tcc_offset = Some(tcc_offset);
+		}
 
-		info.uuid = self.uuid.clone();
+		if let Some(name) = &self.uuid {
+			let policy = info
+				.policy
+				.as_mut()
+				.context("failed to find a dptf device")?;
+			let uuid = policy_uuid(name).unwrap_or(name);
+			match policy.uuids.iter().find(|x| x.eq_ignore_ascii_case(uuid)) {
+				Some(uuid) => policy.uuid.clone_from(uuid),
+				None => bail!(
+					"invalid dptf uuid {name:?}, expected one of {}",
+					policy
+						.uuids
+						.iter()
+						.map(|x| policy_name(x).unwrap_or(x))
+						.collect::<Vec<_>>()
+						.join(", ")
+				),
+			}
+		}
+
+		for (id, value) in &self.odvp {
+			let odvp = info
+				.odvp
+				.iter_mut()
+				.find(|x| x.id == *id)
+				.with_context(|| format!("failed to find odvp{id}"))?;
+			if !odvp.writable && *value != odvp.current {
+				bail!("odvp{id} is read-only on this kernel");
+			}
+			odvp.value = *value;
+		}
+
+		for trip in &self.trip_points {
+			trip.apply(&mut info.zones)?;
+		}
 
 		Ok(())
 	}
 }
 impl From<DptfInfo> for DptfConfig {
 	fn from(value: DptfInfo) -> Self {
+		let mut trip_points: Vec<TripPointConfig> = Vec::new();
+		for zone in &value.zones {
+			// zones are addressed by type, so only the first zone of each type is dumped
+			if !trip_points.iter().any(|x| x.zone == zone.zone_type) {
+				trip_points.extend(TripPointConfig::from_zone(zone));
+			}
+		}
+
 		Self {
 			tcc_offset: value.tcc_offset,
-			uuid: value.uuid,
+			odvp: value
+				.odvp
+				.iter()
+				.filter(|x| x.writable)
+				.map(|x| (x.id, x.value))
+				.collect(),
+			trip_points,
+			// leaves placeholders like INVALID out, apply would reject them
+			uuid: value
+				.policy
+				.filter(|x| {
+					x.uuids
+						.iter()
+						.any(|uuid| uuid.eq_ignore_ascii_case(&x.uuid))
+				})
+				.map(|x| {
+					policy_name(&x.uuid)
+						.map(ToString::to_string)
+						.unwrap_or(x.uuid)
+				}),
 		}
 	}
 }
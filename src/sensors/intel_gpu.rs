@@ -1,74 +1,324 @@
 use std::{
 	fmt::Display,
 	path::{Path, PathBuf},
+	str::FromStr,
+	time::Duration,
 };
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow, bail};
+use log::debug;
 use serde::{Deserialize, Serialize};
 
-use crate::sysfs::{sysfs_exists, sysfs_read, sysfs_write};
+use crate::{
+	sensors::freq_limit::{FreqLimit, FreqRefs},
+	sysfs::{
+		sysfs_exists, sysfs_list, sysfs_read, sysfs_read_link_name, sysfs_read_opt, sysfs_write,
+	},
+};
+
+const INTEL_VENDOR_ID: &str = "0x8086";
+/// Intel integrated graphics always sit at this address
+const INTEGRATED_PCI_SLOT: &str = "0000:00:02.0";
+
+/// Where a driver keeps the frequency files of a GT and what it calls them
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum GpuLayout {
+	/// i915 `gt_*_freq_mhz` files in the card directory, which only cover gt0
+	I915Card,
+	/// i915 `gt/gtN/rps_*_freq_mhz`
+	I915Gt,
+	/// xe `device/tileN/gtN/freq0/*_freq`
+	Xe,
+}
+impl GpuLayout {
+	fn rpn(self) -> &'static str {
+		match self {
+			Self::I915Card => "gt_RPn_freq_mhz",
+			Self::I915Gt => "rps_RPn_freq_mhz",
+			Self::Xe => "rpn_freq",
+		}
+	}
+	fn rp1(self) -> &'static str {
+		match self {
+			Self::I915Card => "gt_RP1_freq_mhz",
+			Self::I915Gt => "rps_RP1_freq_mhz",
+			Self::Xe => "rpe_freq",
+		}
+	}
+	fn rp0(self) -> &'static str {
+		match self {
+			Self::I915Card => "gt_RP0_freq_mhz",
+			Self::I915Gt => "rps_RP0_freq_mhz",
+			Self::Xe => "rp0_freq",
+		}
+	}
+	fn act(self) -> &'static str {
+		match self {
+			Self::I915Card => "gt_act_freq_mhz",
+			Self::I915Gt => "rps_act_freq_mhz",
+			Self::Xe => "act_freq",
+		}
+	}
+	fn min(self) -> &'static str {
+		match self {
+			Self::I915Card => "gt_min_freq_mhz",
+			Self::I915Gt => "rps_min_freq_mhz",
+			Self::Xe => "min_freq",
+		}
+	}
+	fn max(self) -> &'static str {
+		match self {
+			Self::I915Card => "gt_max_freq_mhz",
+			Self::I915Gt => "rps_max_freq_mhz",
+			Self::Xe => "max_freq",
+		}
+	}
+	/// xe has no boost frequency
+	fn boost(self) -> Option<&'static str> {
+		match self {
+			Self::I915Card => Some("gt_boost_freq_mhz"),
+			Self::I915Gt => Some("rps_boost_freq_mhz"),
+			Self::Xe => None,
+		}
+	}
+	/// Relative to the frequency directory
+	fn rc6_residency(self) -> &'static str {
+		match self {
+			Self::I915Card => "power/rc6_residency_ms",
+			Self::I915Gt => "rc6_residency_ms",
+			Self::Xe => "../gtidle/idle_residency_ms",
+		}
+	}
+	/// Directory and file prefix of the per-reason throttle files
+	fn throttle_reasons(self) -> Option<(&'static str, &'static str)> {
+		match self {
+			Self::I915Card => None,
+			Self::I915Gt => Some(("", "throttle_reason_")),
+			Self::Xe => Some(("throttle", "reason_")),
+		}
+	}
+}
 
+/// A single GT (frequency domain) of a GPU
 #[derive(Clone, Debug)]
 pub struct GpuInfo {
+	/// DRM card index, which can change between boots
 	pub id: usize,
+	pub pci_slot: String,
+	pub driver: String,
+	/// `gtN` on i915, `tileN/gtN` on xe
+	pub gt: String,
+	path: PathBuf,
+	layout: GpuLayout,
 	pub hw_min_freq: u64,
 	pub hw_max_freq: u64,
 	pub hw_eff_freq: u64,
 	pub hw_cur_freq: u64,
+	pub rc6_residency: Option<Duration>,
+	/// `None` if the driver doesn't report reasons
+	pub throttle_reasons: Option<Vec<String>>,
 
 	pub min_freq: u64,
 	pub max_freq: u64,
+	pub boost_freq: Option<u64>,
 }
 impl GpuInfo {
-	fn read(id: usize) -> Result<Option<Self>> {
-		let root = PathBuf::from(format!("class/drm/card{id}/"));
+	fn read_throttle_reasons(path: &Path, layout: GpuLayout) -> Result<Option<Vec<String>>> {
+		let Some((dir, prefix)) = layout.throttle_reasons() else {
+			return Ok(None);
+		};
+		let dir = path.join(dir);
+		if !sysfs_exists(&dir.join(format!("{prefix}status")))? {
+			return Ok(None);
+		}
+
+		let mut reasons = Vec::new();
+		for file in sysfs_list(&dir)? {
+			if let Some(reason) = file.strip_prefix(prefix)
+				&& reason != "status"
+				&& sysfs_read::<u8>(&dir.join(&file))? != 0
+			{
+				reasons.push(reason.to_string());
+			}
+		}
 
-		if !sysfs_exists(&root)? {
+		Ok(Some(reasons))
+	}
+
+	/// Reads the energy counter of the whole card in microjoules from the driver's hwmon, if it
+	/// has one
+	pub fn read_energy(&self) -> Result<Option<u64>> {
+		let hwmon = Path::new(&format!("class/drm/card{}", self.id)).join("device/hwmon");
+		if !sysfs_exists(&hwmon)? {
 			return Ok(None);
 		}
 
-		Ok(Some(Self {
+		for dir in sysfs_list(&hwmon)? {
+			if let Some(energy) = sysfs_read_opt(&hwmon.join(dir).join("energy1_input"))? {
+				return Ok(Some(energy));
+			}
+		}
+
+		Ok(None)
+	}
+
+	fn read_gt(
+		id: usize,
+		pci_slot: &str,
+		driver: &str,
+		gt: String,
+		path: PathBuf,
+		layout: GpuLayout,
+	) -> Result<Self> {
+		Ok(Self {
 			id,
-			hw_min_freq: sysfs_read(&root.join("gt_RPn_freq_mhz"))?,
-			hw_eff_freq: sysfs_read(&root.join("gt_RP1_freq_mhz"))?,
-			hw_max_freq: sysfs_read(&root.join("gt_RP0_freq_mhz"))?,
-			hw_cur_freq: sysfs_read(&root.join("gt_act_freq_mhz"))?,
+			pci_slot: pci_slot.to_string(),
+			driver: driver.to_string(),
+			gt,
+			hw_min_freq: sysfs_read(&path.join(layout.rpn()))?,
+			hw_eff_freq: sysfs_read(&path.join(layout.rp1()))?,
+			hw_max_freq: sysfs_read(&path.join(layout.rp0()))?,
+			hw_cur_freq: sysfs_read(&path.join(layout.act()))?,
+			rc6_residency: sysfs_read_opt(&path.join(layout.rc6_residency()))?
+				.map(Duration::from_millis),
+			throttle_reasons: Self::read_throttle_reasons(&path, layout)?,
 
-			min_freq: sysfs_read(&root.join("gt_min_freq_mhz"))?,
-			max_freq: sysfs_read(&root.join("gt_max_freq_mhz"))?,
-		}))
+			min_freq: sysfs_read(&path.join(layout.min()))?,
+			max_freq: sysfs_read(&path.join(layout.max()))?,
+			boost_freq: match layout.boost() {
+				Some(boost) => sysfs_read_opt(&path.join(boost))?,
+				None => None,
+			},
+			path,
+			layout,
+		})
+	}
+
+	fn read(id: usize) -> Result<Vec<Self>> {
+		let root = PathBuf::from(format!("class/drm/card{id}/"));
+
+		// virtual cards like simpledrm, vkms and evdi have no pci vendor
+		let Some(vendor) = sysfs_read_opt::<String>(&root.join("device/vendor"))? else {
+			debug!("skipping card{id} without a pci vendor");
+			return Ok(Vec::new());
+		};
+		if vendor != INTEL_VENDOR_ID {
+			debug!("skipping card{id} with non-intel vendor {vendor}");
+			return Ok(Vec::new());
+		}
+
+		let pci_slot = sysfs_read_link_name(&root.join("device"))?;
+		let driver = sysfs_read_link_name(&root.join("device/driver"))?;
+		let mut gts = Vec::new();
+
+		match driver.as_str() {
+			"i915" if sysfs_exists(&root.join("gt"))? => {
+				for gt in sysfs_list(&root.join("gt"))?
+					.into_iter()
+					.filter(|x| x.starts_with("gt"))
+				{
+					let path = root.join("gt").join(&gt);
+					gts.push(Self::read_gt(
+						id,
+						&pci_slot,
+						&driver,
+						gt,
+						path,
+						GpuLayout::I915Gt,
+					)?);
+				}
+			}
+			"i915" => {
+				gts.push(Self::read_gt(
+					id,
+					&pci_slot,
+					&driver,
+					"gt0".to_string(),
+					root.clone(),
+					GpuLayout::I915Card,
+				)?);
+			}
+			"xe" => {
+				let device = root.join("device");
+				for tile in sysfs_list(&device)?
+					.into_iter()
+					.filter(|x| x.starts_with("tile"))
+				{
+					for gt in sysfs_list(&device.join(&tile))?
+						.into_iter()
+						.filter(|x| x.starts_with("gt"))
+					{
+						let path = device.join(&tile).join(&gt).join("freq0");
+						gts.push(Self::read_gt(
+							id,
+							&pci_slot,
+							&driver,
+							format!("{tile}/{gt}"),
+							path,
+							GpuLayout::Xe,
+						)?);
+					}
+				}
+			}
+			x => debug!("skipping card{id} with unsupported driver {x}"),
+		}
+
+		Ok(gts)
 	}
 
 	pub fn read_all() -> Result<Vec<Self>> {
 		let mut gpus = Vec::new();
-		while let Some(gpu) = Self::read(gpus.len())? {
-			gpus.push(gpu);
-		}
-		if gpus.is_empty()
-			&& let Some(gpu) = Self::read(1)?
-		{
-			gpus.push(gpu);
+
+		// skips connectors like card0-eDP-1 and render nodes
+		let mut ids: Vec<usize> = sysfs_list(Path::new("class/drm"))?
+			.into_iter()
+			.filter_map(|x| x.strip_prefix("card")?.parse().ok())
+			.collect();
+		ids.sort_unstable();
+
+		for id in ids {
+			gpus.extend(Self::read(id)?);
 		}
 
 		Ok(gpus)
 	}
 
+	pub fn integrated(&self) -> bool {
+		self.pci_slot == INTEGRATED_PCI_SLOT
+	}
+
+	fn freq_refs(&self) -> FreqRefs {
+		FreqRefs {
+			min: self.hw_min_freq,
+			base: None,
+			efficient: Some(self.hw_eff_freq),
+			max: self.hw_max_freq,
+			per_mhz: 1,
+		}
+	}
+
 	fn write_min(&self, root: &Path) -> Result<()> {
-		sysfs_write(&root.join("gt_min_freq_mhz"), self.min_freq)
+		sysfs_write(&root.join(self.layout.min()), self.min_freq)
 	}
 
 	fn write_max(&self, root: &Path) -> Result<()> {
-		sysfs_write(&root.join("gt_max_freq_mhz"), self.max_freq)
+		sysfs_write(&root.join(self.layout.max()), self.max_freq)
 	}
 
 	pub fn write(&self) -> Result<()> {
-		let root = PathBuf::from(format!("class/drm/card{}/", self.id));
+		let root = &self.path;
+
+		if self.write_min(root).is_err() {
+			self.write_max(root)?;
+			self.write_min(root)?;
+		}
+		self.write_max(root)?;
 
-		if self.write_min(&root).is_err() {
-			self.write_max(&root)?;
-			self.write_min(&root)?;
+		if let Some(boost) = self.boost_freq
+			&& let Some(file) = self.layout.boost()
+		{
+			sysfs_write(&root.join(file), boost)?;
 		}
-		self.write_max(&root)?;
 
 		Ok(())
 	}
@@ -77,33 +327,161 @@ impl Display for GpuInfo {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		write!(
 			f,
-			"GPU {} ({}-{}MHz, {}MHz efficient): {}-{}MHz -- currently at {}MHz",
+			"GPU {} ({}{}) {} [{}] ({}-{}MHz, {}MHz efficient): {}-{}MHz -- currently at {}MHz",
 			self.id,
+			self.pci_slot,
+			if self.integrated() {
+				", integrated"
+			} else {
+				""
+			},
+			self.gt,
+			self.driver,
 			self.hw_min_freq,
 			self.hw_max_freq,
 			self.hw_eff_freq,
 			self.min_freq,
 			self.max_freq,
 			self.hw_cur_freq,
-		)
+		)?;
+		if let Some(boost) = self.boost_freq {
+			write!(f, ", boost {boost}MHz")?;
+		}
+		if let Some(rc6) = self.rc6_residency {
+			write!(f, "\n\tRC6 residency: {rc6:?}")?;
+		}
+		if let Some(reasons) = &self.throttle_reasons {
+			if reasons.is_empty() {
+				write!(f, "\n\tThrottle reasons: None")?;
+			} else {
+				write!(f, "\n\tThrottle reasons: {}", reasons.join(", "))?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum GpuSelectorRepr {
+	Card(usize),
+	Named(String),
+}
+
+/// `"integrated"`, a PCI slot like `"0000:03:00.0"`, or a DRM card index as a fallback
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "GpuSelectorRepr", into = "GpuSelectorRepr")]
+pub enum GpuSelector {
+	Card(usize),
+	Integrated,
+	PciSlot(String),
+}
+impl GpuSelector {
+	fn matches(&self, gpu: &GpuInfo) -> bool {
+		match self {
+			Self::Card(id) => gpu.id == *id,
+			Self::Integrated => gpu.integrated(),
+			Self::PciSlot(slot) => gpu.pci_slot == *slot,
+		}
+	}
+}
+impl FromStr for GpuSelector {
+	type Err = anyhow::Error;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"integrated" => Ok(Self::Integrated),
+			// the domain is usually omitted by lspci
+			x if x.matches(':').count() == 1 => Ok(Self::PciSlot(format!("0000:{x}"))),
+			x if x.matches(':').count() == 2 => Ok(Self::PciSlot(x.to_string())),
+			x => Err(anyhow!("invalid gpu selector: {x}")),
+		}
+	}
+}
+impl Display for GpuSelector {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Card(id) => write!(f, "card{id}"),
+			Self::Integrated => write!(f, "integrated"),
+			Self::PciSlot(slot) => write!(f, "{slot}"),
+		}
+	}
+}
+impl TryFrom<GpuSelectorRepr> for GpuSelector {
+	type Error = anyhow::Error;
+	fn try_from(value: GpuSelectorRepr) -> Result<Self, Self::Error> {
+		match value {
+			GpuSelectorRepr::Card(id) => Ok(Self::Card(id)),
+			GpuSelectorRepr::Named(name) => name.parse(),
+		}
+	}
+}
+impl From<GpuSelector> for GpuSelectorRepr {
+	fn from(value: GpuSelector) -> Self {
+		match value {
+			GpuSelector::Card(id) => Self::Card(id),
+			x => Self::Named(x.to_string()),
+		}
 	}
 }
 
 #[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct GpuConfig {
-	pub id: usize,
-	pub min_freq: u64,
-	pub max_freq: u64,
+	pub id: GpuSelector,
+	/// Every GT of the GPU if unset
+	#[serde(default)]
+	pub gt: Option<String>,
+	/// MHz, or relative to the GPU's hardware frequencies
+	pub min_freq: FreqLimit,
+	/// MHz, or relative to the GPU's hardware frequencies
+	pub max_freq: FreqLimit,
+	/// MHz, or relative to the GPU's hardware frequencies, i915 only
+	#[serde(default)]
+	pub boost_freq: Option<FreqLimit>,
 }
 impl GpuConfig {
 	pub fn apply(&self, gpus: &mut [GpuInfo]) -> Result<()> {
-		let gpu = gpus
+		let mut found = false;
+
+		for gpu in gpus
 			.iter_mut()
-			.find(|x| x.id == self.id)
-			.with_context(|| format!("failed to find gpu with id {}", self.id))?;
+			.filter(|x| self.id.matches(x) && self.gt.as_ref().is_none_or(|gt| *gt == x.gt))
+		{
+			let refs = gpu.freq_refs();
+			gpu.max_freq = self
+				.max_freq
+				.resolve(&refs)
+				.with_context(|| format!("invalid max frequency for gpu {} {}", gpu.id, gpu.gt))?;
+			gpu.min_freq = self
+				.min_freq
+				.resolve(&refs)
+				.with_context(|| format!("invalid min frequency for gpu {} {}", gpu.id, gpu.gt))?;
+			if gpu.min_freq > gpu.max_freq {
+				bail!(
+					"invalid frequency limits for gpu {} {}: min {}MHz is above max {}MHz",
+					gpu.id,
+					gpu.gt,
+					gpu.min_freq,
+					gpu.max_freq
+				);
+			}
+			if let Some(boost_freq) = &self.boost_freq {
+				if gpu.boost_freq.is_none() {
+					bail!("gpu {} {} has no boost frequency", gpu.id, gpu.gt);
+				}
+				gpu.boost_freq = Some(boost_freq.resolve(&refs).with_context(|| {
+					format!("invalid boost frequency for gpu {} {}", gpu.id, gpu.gt)
+				})?);
+			}
+			found = true;
+		}
 
-		gpu.max_freq = self.max_freq;
-		gpu.min_freq = self.min_freq;
+		if !found {
+			match &self.gt {
+				Some(gt) => bail!("failed to find gpu {} with gt {gt}", self.id),
+				None => bail!("failed to find gpu {}", self.id),
+			}
+		}
 
 		Ok(())
 	}
@@ -111,9 +489,15 @@ impl GpuConfig {
 impl From<GpuInfo> for GpuConfig {
 	fn from(value: GpuInfo) -> Self {
 		Self {
-			id: value.id,
-			max_freq: value.max_freq,
-			min_freq: value.min_freq,
+			id: if value.integrated() {
+				GpuSelector::Integrated
+			} else {
+				GpuSelector::PciSlot(value.pci_slot)
+			},
+			gt: Some(value.gt),
+			max_freq: FreqLimit::Absolute(value.max_freq),
+			min_freq: FreqLimit::Absolute(value.min_freq),
+			boost_freq: value.boost_freq.map(FreqLimit::Absolute),
 		}
 	}
 }
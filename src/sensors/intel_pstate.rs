@@ -3,59 +3,120 @@ use std::{
 	path::{Path, PathBuf},
 };
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
+use log::debug;
 use serde::{Deserialize, Serialize};
 
 use crate::{
 	msr::{Msr, msr_get_bit, msr_read, msr_set_bit, msr_write},
-	sysfs::{sysfs_exists, sysfs_read, sysfs_write},
+	sensors::{
+		cpu_topology::{CpuSelector, CpuTopology},
+		freq_limit::{FreqLimit, FreqRefs},
+	},
+	sysfs::{
+		format_cpulist, sysfs_exists, sysfs_read, sysfs_read_cpulist, sysfs_read_opt, sysfs_write,
+	},
 };
 
+/// Values `intel_pstate/status` reports and accepts
+const PSTATE_STATUSES: &[&str] = &["active", "passive", "off"];
+
+const EPB_NAMES: &[(&str, u8)] = &[
+	("performance", 0),
+	("balance-performance", 4),
+	("normal", 6),
+	("balance-power", 8),
+	("power", 15),
+];
+
+/// Checks a value against a list the kernel offers, an empty list means the kernel doesn't tell us
+fn validate_available(name: &str, value: &str, available: &[String]) -> Result<()> {
+	if !available.is_empty() && !available.iter().any(|x| x == value) {
+		bail!("invalid {name} {value:?}, available: {available:?}");
+	}
+	Ok(())
+}
+
+/// Driver specific settings only exist with some drivers, driver modes and CPUs, so they are
+/// skipped if absent
+fn apply_optional<T: Clone>(name: &str, info: &mut Option<T>, cfg: &Option<T>) {
+	if let Some(cfg) = cfg {
+		if info.is_some() {
+			*info = Some(cfg.clone());
+		} else {
+			debug!("{name} is unavailable, skipping");
+		}
+	}
+}
+
+/// Per-cpu cpufreq state. Only the generic cpufreq files are required, everything that depends on
+/// the scaling driver or on `/dev/cpu/*/msr` is optional.
 #[derive(Clone, Debug)]
 pub struct PstateCpuInfo {
 	pub id: usize,
+	pub driver: String,
 	pub hw_max_freq: u64,
 	pub hw_min_freq: u64,
-	pub hw_base_freq: u64,
+	pub hw_base_freq: Option<u64>,
 	pub hw_current_freq: u64,
 
+	pub available_governors: Vec<String>,
+	pub available_epps: Vec<String>,
+
 	pub governor: String,
-	pub epp: String,
-	pub epb: u64,
+	/// Only exposed by intel_pstate in active mode
+	pub epp: Option<String>,
+	pub epb: Option<u64>,
 	pub max_freq: u64,
 	pub min_freq: u64,
 
-	pub ctdp: u64,
-	pub bdprochot: bool,
+	pub ctdp: Option<u64>,
+	pub bdprochot: Option<bool>,
 }
 impl PstateCpuInfo {
-	fn read(id: usize) -> Result<Option<Self>> {
+	fn read(id: usize) -> Result<Self> {
 		let root = PathBuf::from(format!("devices/system/cpu/cpu{id}/"));
 		let freq = root.join("cpufreq");
 		let power = root.join("power");
 
-		if !sysfs_exists(&root)? {
-			return Ok(None);
-		}
-
-		let power_ctl = msr_read(id, Msr::PowerCtl)?;
-
-		Ok(Some(Self {
+		Ok(Self {
 			id,
+			driver: sysfs_read(&freq.join("scaling_driver"))?,
 			hw_max_freq: sysfs_read(&freq.join("cpuinfo_max_freq"))?,
 			hw_min_freq: sysfs_read(&freq.join("cpuinfo_min_freq"))?,
-			hw_base_freq: sysfs_read(&freq.join("base_frequency"))?,
+			hw_base_freq: sysfs_read_opt(&freq.join("base_frequency"))?,
 			hw_current_freq: sysfs_read(&freq.join("scaling_cur_freq"))?,
 
+			available_governors: sysfs_read_opt::<String>(
+				&freq.join("scaling_available_governors"),
+			)?
+			.map(|x| x.split_whitespace().map(ToOwned::to_owned).collect())
+			.unwrap_or_default(),
+			available_epps: sysfs_read_opt::<String>(
+				&freq.join("energy_performance_available_preferences"),
+			)?
+			.map(|x| x.split_whitespace().map(ToOwned::to_owned).collect())
+			.unwrap_or_default(),
+
 			governor: sysfs_read(&freq.join("scaling_governor"))?,
-			epp: sysfs_read(&freq.join("energy_performance_preference"))?,
-			epb: sysfs_read(&power.join("energy_perf_bias"))?,
+			epp: sysfs_read_opt(&freq.join("energy_performance_preference"))?,
+			epb: sysfs_read_opt(&power.join("energy_perf_bias"))?,
 			max_freq: sysfs_read(&freq.join("scaling_max_freq"))?,
 			min_freq: sysfs_read(&freq.join("scaling_min_freq"))?,
 
-			ctdp: msr_read(id, Msr::ConfigTdpControl)?,
-			bdprochot: msr_get_bit(power_ctl, 0),
-		}))
+			ctdp: msr_read(id, Msr::ConfigTdpControl).ok(),
+			bdprochot: msr_read(id, Msr::PowerCtl).ok().map(|x| msr_get_bit(x, 0)),
+		})
+	}
+
+	fn freq_refs(&self) -> FreqRefs {
+		FreqRefs {
+			min: self.hw_min_freq,
+			base: self.hw_base_freq,
+			efficient: None,
+			max: self.hw_max_freq,
+			per_mhz: 1000,
+		}
 	}
 
 	fn write_min(&self, root: &Path) -> Result<()> {
@@ -71,13 +132,21 @@ impl PstateCpuInfo {
 		let power = root.join("power");
 
 		sysfs_write(&freq.join("scaling_governor"), &self.governor)?;
-		sysfs_write(&freq.join("energy_performance_preference"), &self.epp)?;
-		sysfs_write(&power.join("energy_perf_bias"), self.epb)?;
-		msr_write(self.id, Msr::ConfigTdpControl, self.ctdp)?;
+		if let Some(epp) = &self.epp {
+			sysfs_write(&freq.join("energy_performance_preference"), epp)?;
+		}
+		if let Some(epb) = self.epb {
+			sysfs_write(&power.join("energy_perf_bias"), epb)?;
+		}
+		if let Some(ctdp) = self.ctdp {
+			msr_write(self.id, Msr::ConfigTdpControl, ctdp)?;
+		}
 
-		let mut power_ctl = msr_read(self.id, Msr::PowerCtl)?;
-		power_ctl = msr_set_bit(power_ctl, 0, self.bdprochot);
-		msr_write(self.id, Msr::PowerCtl, power_ctl)?;
+		if let Some(bdprochot) = self.bdprochot {
+			let mut power_ctl = msr_read(self.id, Msr::PowerCtl)?;
+			power_ctl = msr_set_bit(power_ctl, 0, bdprochot);
+			msr_write(self.id, Msr::PowerCtl, power_ctl)?;
+		}
 
 		if self.write_min(&freq).is_err() {
 			self.write_max(&freq)?;
@@ -92,20 +161,35 @@ impl Display for PstateCpuInfo {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		write!(
 			f,
-			"CPU {} ({}-{}MHz, {}MHz without turbo): \"{}\" governor, \"{}\" epp, {} epb, {} cTDP, bdprochot {}, {}-{}MHz -- currently at {}MHz",
+			"CPU {} [{}] ({}-{}MHz",
 			self.id,
+			self.driver,
 			self.hw_min_freq / 1000,
 			self.hw_max_freq / 1000,
-			self.hw_base_freq / 1000,
-			&self.governor,
-			&self.epp,
-			self.epb,
-			self.ctdp,
-			if self.bdprochot {
-				"enabled"
-			} else {
-				"disabled"
-			},
+		)?;
+		if let Some(base_freq) = self.hw_base_freq {
+			write!(f, ", {}MHz without turbo", base_freq / 1000)?;
+		}
+		write!(f, "): \"{}\" governor", self.governor)?;
+		if let Some(epp) = &self.epp {
+			write!(f, ", \"{epp}\" epp")?;
+		}
+		if let Some(epb) = self.epb {
+			write!(f, ", {epb} epb")?;
+		}
+		if let Some(ctdp) = self.ctdp {
+			write!(f, ", {ctdp} cTDP")?;
+		}
+		if let Some(bdprochot) = self.bdprochot {
+			write!(
+				f,
+				", bdprochot {}",
+				if bdprochot { "enabled" } else { "disabled" }
+			)?;
+		}
+		write!(
+			f,
+			", {}-{}MHz -- currently at {}MHz",
 			self.min_freq / 1000,
 			self.max_freq / 1000,
 			self.hw_current_freq / 1000,
@@ -116,77 +200,331 @@ impl Display for PstateCpuInfo {
 #[derive(Clone, Debug)]
 pub struct PstateInfo {
 	pub cpus: Vec<PstateCpuInfo>,
-	pub turbo: bool,
+	/// Present CPUs that are currently offline
+	pub offline: Vec<usize>,
+	pub topology: CpuTopology,
+	current_status: Option<String>,
+	pub num_pstates: Option<u64>,
+
+	pub status: Option<String>,
+	/// intel_pstate `no_turbo` or cpufreq `boost`
+	pub turbo: Option<bool>,
+	pub hwp_dynamic_boost: Option<bool>,
+	pub min_perf_pct: Option<u64>,
+	pub max_perf_pct: Option<u64>,
+	pub energy_efficiency: Option<bool>,
 }
 impl PstateInfo {
 	pub fn read() -> Result<Self> {
+		let root = Path::new("devices/system/cpu/intel_pstate");
+		let online = sysfs_read_cpulist(Path::new("devices/system/cpu/online"))?;
+
 		let mut cpus = Vec::new();
-		while let Some(cpu) = PstateCpuInfo::read(cpus.len())? {
-			cpus.push(cpu);
+		let mut offline = Vec::new();
+		for id in sysfs_read_cpulist(Path::new("devices/system/cpu/present"))? {
+			if online.contains(&id) {
+				cpus.push(
+					PstateCpuInfo::read(id).with_context(|| format!("failed to read cpu {id}"))?,
+				);
+			} else {
+				offline.push(id);
+			}
 		}
 
+		let status = sysfs_read_opt(&root.join("status"))?;
+
 		Ok(Self {
 			cpus,
-			turbo: sysfs_read::<usize>(Path::new("devices/system/cpu/intel_pstate/no_turbo"))? == 0,
+			offline,
+			topology: CpuTopology::read()?,
+			current_status: status.clone(),
+			num_pstates: sysfs_read_opt(&root.join("num_pstates"))?,
+
+			status,
+			turbo: if let Some(no_turbo) = sysfs_read_opt::<usize>(&root.join("no_turbo"))? {
+				Some(no_turbo == 0)
+			} else {
+				sysfs_read_opt::<usize>(Path::new("devices/system/cpu/cpufreq/boost"))?
+					.map(|x| x == 1)
+			},
+			hwp_dynamic_boost: sysfs_read_opt::<usize>(&root.join("hwp_dynamic_boost"))?
+				.map(|x| x == 1),
+			min_perf_pct: sysfs_read_opt(&root.join("min_perf_pct"))?,
+			max_perf_pct: sysfs_read_opt(&root.join("max_perf_pct"))?,
+			energy_efficiency: sysfs_read_opt::<usize>(&root.join("energy_efficiency"))?
+				.map(|x| x == 1),
 		})
 	}
 
+	/// Returns whether the driver status changed, which recreates every cpufreq policy and changes
+	/// the meaning of governors and epp
+	pub fn write_status(&self) -> Result<bool> {
+		if let Some(status) = &self.status
+			&& self.current_status.as_ref() != Some(status)
+		{
+			sysfs_write(Path::new("devices/system/cpu/intel_pstate/status"), status)?;
+			Ok(true)
+		} else {
+			Ok(false)
+		}
+	}
+
+	fn write_min_perf_pct(&self, root: &Path) -> Result<()> {
+		if let Some(min_perf_pct) = self.min_perf_pct {
+			sysfs_write(&root.join("min_perf_pct"), min_perf_pct)?;
+		}
+		Ok(())
+	}
+	fn write_max_perf_pct(&self, root: &Path) -> Result<()> {
+		if let Some(max_perf_pct) = self.max_perf_pct {
+			sysfs_write(&root.join("max_perf_pct"), max_perf_pct)?;
+		}
+		Ok(())
+	}
+
 	pub fn write(&self) -> Result<()> {
+		let root = Path::new("devices/system/cpu/intel_pstate");
+
+		self.write_status()?;
+
+		if let Some(hwp_dynamic_boost) = self.hwp_dynamic_boost {
+			sysfs_write(
+				&root.join("hwp_dynamic_boost"),
+				if hwp_dynamic_boost { 1 } else { 0 },
+			)?;
+		}
+		if let Some(energy_efficiency) = self.energy_efficiency {
+			sysfs_write(
+				&root.join("energy_efficiency"),
+				if energy_efficiency { 1 } else { 0 },
+			)?;
+		}
+
+		if self.write_min_perf_pct(root).is_err() {
+			self.write_max_perf_pct(root)?;
+			self.write_min_perf_pct(root)?;
+		}
+		self.write_max_perf_pct(root)?;
+
 		for cpu in &self.cpus {
 			cpu.write()?;
 		}
 
-		sysfs_write(
-			Path::new("devices/system/cpu/intel_pstate/no_turbo"),
-			if self.turbo { 0 } else { 1 },
-		)?;
+		if let Some(turbo) = self.turbo {
+			if sysfs_exists(&root.join("no_turbo"))? {
+				sysfs_write(&root.join("no_turbo"), if turbo { 0 } else { 1 })?;
+			} else {
+				sysfs_write(
+					Path::new("devices/system/cpu/cpufreq/boost"),
+					if turbo { 1 } else { 0 },
+				)?;
+			}
+		}
 
 		Ok(())
 	}
 }
 impl Display for PstateInfo {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		write!(f, "Set of {} CPUs: ", self.cpus.len())?;
-		if self.turbo {
-			writeln!(f, "turbo enabled")?;
+		write!(f, "intel_pstate ")?;
+		if let Some(status) = &self.status {
+			write!(f, "\"{status}\"")?;
 		} else {
-			writeln!(f, "turbo disabled")?;
+			write!(f, "unavailable")?;
+		}
+		if let Some(num_pstates) = self.num_pstates {
+			write!(f, " ({num_pstates} P-states)")?;
 		}
+		if let (Some(min), Some(max)) = (self.min_perf_pct, self.max_perf_pct) {
+			write!(f, ", {min}-{max}% performance")?;
+		}
+		if let Some(hwp_dynamic_boost) = self.hwp_dynamic_boost {
+			write!(
+				f,
+				", HWP dynamic boost {}",
+				if hwp_dynamic_boost {
+					"enabled"
+				} else {
+					"disabled"
+				}
+			)?;
+		}
+		if let Some(energy_efficiency) = self.energy_efficiency {
+			write!(
+				f,
+				", energy efficiency optimization {}",
+				if energy_efficiency {
+					"enabled"
+				} else {
+					"disabled"
+				}
+			)?;
+		}
+		writeln!(f)?;
 
-		for cpu in &self.cpus {
-			writeln!(f, "{cpu}")?;
+		write!(f, "Set of {} CPUs: ", self.cpus.len())?;
+		if !self.offline.is_empty() {
+			write!(f, "{:?} offline, ", self.offline)?;
+		}
+		match self.turbo {
+			Some(true) => writeln!(f, "turbo enabled")?,
+			Some(false) => writeln!(f, "turbo disabled")?,
+			None => writeln!(f, "turbo not controllable")?,
+		}
+
+		if self.topology.hybrid() {
+			for (name, ids) in [
+				("P-cores", &self.topology.pcores),
+				("E-cores", &self.topology.ecores),
+			] {
+				writeln!(f, "{name} ({}):", format_cpulist(ids))?;
+				for cpu in self.cpus.iter().filter(|x| ids.contains(&x.id)) {
+					writeln!(f, "{cpu}")?;
+				}
+			}
+		} else {
+			for cpu in &self.cpus {
+				writeln!(f, "{cpu}")?;
+			}
 		}
 
 		Ok(())
 	}
 }
 
+/// An energy performance preference name from `energy_performance_available_preferences`, or a
+/// raw HWP EPP value
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Epp {
+	Raw(u8),
+	Named(String),
+}
+impl Epp {
+	fn resolve(&self, available: &[String], validate: bool) -> Result<String> {
+		match self {
+			Self::Raw(epp) => Ok(epp.to_string()),
+			Self::Named(epp) => {
+				if validate {
+					validate_available("epp", epp, available)?;
+				}
+				Ok(epp.clone())
+			}
+		}
+	}
+}
+impl From<String> for Epp {
+	fn from(value: String) -> Self {
+		value.parse().map_or(Self::Named(value), Self::Raw)
+	}
+}
+
+/// An energy performance bias from 0 (performance) to 15 (power), or one of the names the kernel
+/// accepts for it
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Epb {
+	Raw(u8),
+	Named(String),
+}
+impl Epb {
+	fn resolve(&self) -> Result<u64> {
+		match self {
+			Self::Raw(epb @ 0..=15) => Ok(*epb as u64),
+			Self::Raw(epb) => bail!("invalid epb {epb}, expected 0-15"),
+			Self::Named(epb) => EPB_NAMES
+				.iter()
+				.find(|(name, _)| name == epb)
+				.map(|(_, val)| *val as u64)
+				.with_context(|| {
+					format!(
+						"invalid epb {epb:?}, expected one of {:?}",
+						EPB_NAMES.iter().map(|(name, _)| *name).collect::<Vec<_>>()
+					)
+				}),
+		}
+	}
+}
+
 #[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct PstateCpuConfig {
-	pub ids: Vec<usize>,
+	pub ids: Vec<CpuSelector>,
 	pub governor: String,
-	pub epp: String,
-	pub epb: u64,
-	pub max_freq: u64,
-	pub min_freq: u64,
-	pub ctdp: u64,
-	pub bdprochot: bool,
+	/// Driver specific settings are ignored on cpus that don't expose them, e.g. epp with
+	/// intel_pstate in passive mode or anything MSR based without the msr module
+	#[serde(default)]
+	pub epp: Option<Epp>,
+	#[serde(default)]
+	pub epb: Option<Epb>,
+	/// kHz, or relative to the cpu's hardware frequencies
+	pub max_freq: FreqLimit,
+	/// kHz, or relative to the cpu's hardware frequencies
+	pub min_freq: FreqLimit,
+	#[serde(default)]
+	pub ctdp: Option<u64>,
+	#[serde(default)]
+	pub bdprochot: Option<bool>,
 }
 impl PstateCpuConfig {
-	pub fn apply(&self, cpus: &mut [PstateCpuInfo]) -> Result<()> {
-		for id in &self.ids {
-			let cpu = cpus
+	pub fn apply(&self, info: &mut PstateInfo) -> Result<()> {
+		// governors and epps offered by the kernel change with the intel_pstate status, so they can
+		// only be validated once the status has been written and everything has been reread
+		let validate = info.status == info.current_status;
+
+		let epb = self.epb.as_ref().map(Epb::resolve).transpose()?;
+
+		for id in &info.topology.resolve(&self.ids) {
+			// gets applied by the next poll once the cpu is back online
+			if info.offline.contains(id) {
+				debug!("cpu {id} is offline, skipping");
+				continue;
+			}
+
+			let cpu = info
+				.cpus
 				.iter_mut()
 				.find(|x| x.id == *id)
 				.with_context(|| format!("failed to find cpu with id {id}"))?;
 
+			if validate {
+				validate_available(
+					&format!("cpu {id} governor"),
+					&self.governor,
+					&cpu.available_governors,
+				)?;
+			}
+			let epp = self
+				.epp
+				.as_ref()
+				.map(|x| x.resolve(&cpu.available_epps, validate))
+				.transpose()
+				.with_context(|| format!("invalid config for cpu {id}"))?;
+
 			cpu.governor.clone_from(&self.governor);
-			cpu.epp.clone_from(&self.epp);
-			cpu.epb = self.epb;
-			cpu.max_freq = self.max_freq;
-			cpu.min_freq = self.min_freq;
-			cpu.ctdp = self.ctdp;
-			cpu.bdprochot = self.bdprochot;
+			apply_optional(&format!("cpu {id} epp"), &mut cpu.epp, &epp);
+			apply_optional(&format!("cpu {id} epb"), &mut cpu.epb, &epb);
+			let refs = cpu.freq_refs();
+			cpu.max_freq = self
+				.max_freq
+				.resolve(&refs)
+				.with_context(|| format!("invalid max frequency for cpu {id}"))?;
+			cpu.min_freq = self
+				.min_freq
+				.resolve(&refs)
+				.with_context(|| format!("invalid min frequency for cpu {id}"))?;
+			if cpu.min_freq > cpu.max_freq {
+				bail!(
+					"invalid frequency limits for cpu {id}: min {}MHz is above max {}MHz",
+					cpu.min_freq / 1000,
+					cpu.max_freq / 1000
+				);
+			}
+			apply_optional(&format!("cpu {id} cTDP"), &mut cpu.ctdp, &self.ctdp);
+			apply_optional(
+				&format!("cpu {id} bdprochot"),
+				&mut cpu.bdprochot,
+				&self.bdprochot,
+			);
 		}
 
 		Ok(())
@@ -195,12 +533,12 @@ impl PstateCpuConfig {
 impl From<PstateCpuInfo> for PstateCpuConfig {
 	fn from(value: PstateCpuInfo) -> Self {
 		Self {
-			ids: vec![value.id],
+			ids: vec![CpuSelector::Id(value.id)],
 			governor: value.governor,
-			epp: value.epp,
-			epb: value.epb,
-			max_freq: value.max_freq,
-			min_freq: value.min_freq,
+			epp: value.epp.map(Into::into),
+			epb: value.epb.map(|x| Epb::Raw(x as u8)),
+			max_freq: FreqLimit::Absolute(value.max_freq),
+			min_freq: FreqLimit::Absolute(value.min_freq),
 			ctdp: value.ctdp,
 			bdprochot: value.bdprochot,
 		}
@@ -210,24 +548,95 @@ impl From<PstateCpuInfo> for PstateCpuConfig {
 #[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct PstateConfig {
 	pub cpus: Vec<PstateCpuConfig>,
-	pub turbo: bool,
+	#[serde(default)]
+	pub turbo: Option<bool>,
+	#[serde(default)]
+	pub status: Option<String>,
+	#[serde(default)]
+	pub hwp_dynamic_boost: Option<bool>,
+	#[serde(default)]
+	pub min_perf_pct: Option<u64>,
+	#[serde(default)]
+	pub max_perf_pct: Option<u64>,
+	#[serde(default)]
+	pub energy_efficiency: Option<bool>,
 }
 impl PstateConfig {
 	pub fn apply(&self, info: &mut PstateInfo) -> Result<()> {
+		if let Some(status) = &self.status {
+			if !PSTATE_STATUSES.contains(&status.as_str()) {
+				bail!(
+					"invalid intel_pstate status {status:?}, expected one of {PSTATE_STATUSES:?}"
+				);
+			}
+			if info.status.is_none() {
+				bail!("intel_pstate is unavailable");
+			}
+			info.status = Some(status.clone());
+		}
+
+		apply_optional(
+			"intel_pstate hwp_dynamic_boost",
+			&mut info.hwp_dynamic_boost,
+			&self.hwp_dynamic_boost,
+		);
+		apply_optional(
+			"intel_pstate min_perf_pct",
+			&mut info.min_perf_pct,
+			&self.min_perf_pct,
+		);
+		apply_optional(
+			"intel_pstate max_perf_pct",
+			&mut info.max_perf_pct,
+			&self.max_perf_pct,
+		);
+		apply_optional(
+			"intel_pstate energy_efficiency",
+			&mut info.energy_efficiency,
+			&self.energy_efficiency,
+		);
+
 		for cpu in &self.cpus {
-			cpu.apply(&mut info.cpus)?;
+			cpu.apply(info)?;
 		}
 
-		info.turbo = self.turbo;
+		apply_optional("turbo", &mut info.turbo, &self.turbo);
 
 		Ok(())
 	}
 }
 impl From<PstateInfo> for PstateConfig {
 	fn from(value: PstateInfo) -> Self {
+		// merge cpus with identical settings into one entry
+		let mut groups: Vec<(Vec<usize>, PstateCpuConfig)> = Vec::new();
+		for cpu in value.cpus {
+			let id = cpu.id;
+			let cfg = PstateCpuConfig {
+				ids: Vec::new(),
+				..cpu.into()
+			};
+
+			if let Some((ids, _)) = groups.iter_mut().find(|(_, x)| *x == cfg) {
+				ids.push(id);
+			} else {
+				groups.push((vec![id], cfg));
+			}
+		}
+
 		Self {
-			cpus: value.cpus.into_iter().map(Into::into).collect(),
+			cpus: groups
+				.into_iter()
+				.map(|(ids, cfg)| PstateCpuConfig {
+					ids: value.topology.selectors(&ids),
+					..cfg
+				})
+				.collect(),
 			turbo: value.turbo,
+			status: value.status,
+			hwp_dynamic_boost: value.hwp_dynamic_boost,
+			min_perf_pct: value.min_perf_pct,
+			max_perf_pct: value.max_perf_pct,
+			energy_efficiency: value.energy_efficiency,
 		}
 	}
 }
@@ -7,7 +7,7 @@ use std::{
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 
-use crate::sysfs::{sysfs_exists, sysfs_read, sysfs_write};
+use crate::sysfs::{sysfs_exists, sysfs_read, sysfs_read_opt, sysfs_write};
 
 #[derive(Clone, Debug)]
 pub struct RaplConstraintInfo {
@@ -74,6 +74,10 @@ pub struct RaplZoneInfo {
 	pub path: PathBuf,
 	pub name: String,
 	pub enabled: bool,
+	/// µJ, `None` if the counter can't be read (it is root-only on newer kernels)
+	pub energy: Option<u64>,
+	/// µJ, the energy counter wraps around at this value
+	pub max_energy_range: Option<u64>,
 	pub constraints: Vec<RaplConstraintInfo>,
 	pub subzones: Vec<RaplZoneInfo>,
 }
@@ -106,12 +110,24 @@ impl RaplZoneInfo {
 				sysfs_read::<String>(&zone_path.join("name"))?
 			),
 			enabled: sysfs_read::<usize>(&zone_path.join("enabled"))? == 1,
+			energy: sysfs_read(&zone_path.join("energy_uj")).ok(),
+			max_energy_range: sysfs_read(&zone_path.join("max_energy_range_uj")).ok(),
 			path: zone_path,
 			constraints,
 			subzones,
 		}))
 	}
 
+	/// Rereads the energy counter in µJ
+	pub fn read_energy(&self) -> Result<Option<u64>> {
+		sysfs_read_opt(&self.path.join("energy_uj"))
+	}
+
+	/// Whether this is an MMIO zone, which reports the same package power as its MSR counterpart
+	pub fn mmio(&self) -> bool {
+		self.name.starts_with("intel-rapl-mmio:")
+	}
+
 	pub fn write(&self) -> Result<()> {
 		sysfs_write(&self.path.join("enabled"), if self.enabled { 1 } else { 0 })?;
 
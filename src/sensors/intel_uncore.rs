@@ -0,0 +1,192 @@
+use std::{
+	fmt::Display,
+	path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	sensors::freq_limit::{FreqLimit, FreqRefs},
+	sysfs::{sysfs_exists, sysfs_list, sysfs_read, sysfs_read_opt, sysfs_write},
+};
+
+const UNCORE_ROOT: &str = "devices/system/cpu/intel_uncore_frequency";
+
+#[derive(Clone, Debug)]
+pub struct UncoreInfo {
+	pub path: PathBuf,
+	pub package: usize,
+	/// Die on the legacy interface, power domain on TPMI
+	pub die: usize,
+	pub hw_min_freq: u64,
+	pub hw_max_freq: u64,
+	pub hw_current_freq: Option<u64>,
+
+	pub min_freq: u64,
+	pub max_freq: u64,
+}
+impl UncoreInfo {
+	fn read(path: PathBuf) -> Result<Self> {
+		let name = path
+			.file_name()
+			.context("unable to get uncore domain name")?
+			.to_str()
+			.context("invalid uncore domain name")?;
+
+		// tpmi exposes the ids as files, the legacy interface only in the name
+		let (package, die) = if sysfs_exists(&path.join("package_id"))? {
+			(
+				sysfs_read(&path.join("package_id"))?,
+				sysfs_read(&path.join("domain_id"))?,
+			)
+		} else {
+			let (package, die) = name
+				.strip_prefix("package_")
+				.and_then(|x| x.split_once("_die_"))
+				.with_context(|| format!("invalid uncore domain name {name:?}"))?;
+			(package.parse()?, die.parse()?)
+		};
+
+		Ok(Self {
+			package,
+			die,
+			hw_min_freq: sysfs_read(&path.join("initial_min_freq_khz"))?,
+			hw_max_freq: sysfs_read(&path.join("initial_max_freq_khz"))?,
+			hw_current_freq: sysfs_read_opt(&path.join("current_freq_khz"))?,
+
+			min_freq: sysfs_read(&path.join("min_freq_khz"))?,
+			max_freq: sysfs_read(&path.join("max_freq_khz"))?,
+			path,
+		})
+	}
+
+	pub fn read_all() -> Result<Vec<Self>> {
+		let root = Path::new(UNCORE_ROOT);
+
+		if !sysfs_exists(root)? {
+			return Ok(Vec::new());
+		}
+
+		let entries = sysfs_list(root)?;
+		// tpmi kernels also create package_XX_die_XX directories for compatibility
+		let tpmi = entries.iter().any(|x| x.starts_with("uncore"));
+
+		entries
+			.into_iter()
+			.filter(|x| {
+				if tpmi {
+					x.starts_with("uncore")
+				} else {
+					x.starts_with("package_")
+				}
+			})
+			.map(|x| Self::read(root.join(x)))
+			.collect()
+	}
+
+	fn freq_refs(&self) -> FreqRefs {
+		FreqRefs {
+			min: self.hw_min_freq,
+			base: None,
+			efficient: None,
+			max: self.hw_max_freq,
+			per_mhz: 1000,
+		}
+	}
+
+	fn write_min(&self) -> Result<()> {
+		sysfs_write(&self.path.join("min_freq_khz"), self.min_freq)
+	}
+	fn write_max(&self) -> Result<()> {
+		sysfs_write(&self.path.join("max_freq_khz"), self.max_freq)
+	}
+
+	pub fn write(&self) -> Result<()> {
+		if self.write_min().is_err() {
+			self.write_max()?;
+			self.write_min()?;
+		}
+		self.write_max()?;
+
+		Ok(())
+	}
+}
+impl Display for UncoreInfo {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"Uncore package {} die {} ({}-{}MHz): {}-{}MHz",
+			self.package,
+			self.die,
+			self.hw_min_freq / 1000,
+			self.hw_max_freq / 1000,
+			self.min_freq / 1000,
+			self.max_freq / 1000,
+		)?;
+		if let Some(current) = self.hw_current_freq {
+			write!(f, " -- currently at {}MHz", current / 1000)?;
+		}
+
+		Ok(())
+	}
+}
+
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct UncoreConfig {
+	/// Every package if unset
+	#[serde(default)]
+	pub package: Option<usize>,
+	/// Every die/domain if unset
+	#[serde(default)]
+	pub die: Option<usize>,
+	/// kHz, or relative to the domain's hardware frequencies
+	pub min_freq: FreqLimit,
+	/// kHz, or relative to the domain's hardware frequencies
+	pub max_freq: FreqLimit,
+}
+impl UncoreConfig {
+	pub fn apply(&self, domains: &mut [UncoreInfo]) -> Result<()> {
+		let mut found = false;
+
+		for domain in domains.iter_mut().filter(|x| {
+			self.package.is_none_or(|package| x.package == package)
+				&& self.die.is_none_or(|die| x.die == die)
+		}) {
+			let refs = domain.freq_refs();
+			domain.min_freq = self.min_freq.resolve(&refs).with_context(|| {
+				format!(
+					"invalid min frequency for uncore package {} die {}",
+					domain.package, domain.die
+				)
+			})?;
+			domain.max_freq = self.max_freq.resolve(&refs).with_context(|| {
+				format!(
+					"invalid max frequency for uncore package {} die {}",
+					domain.package, domain.die
+				)
+			})?;
+			found = true;
+		}
+
+		if !found {
+			bail!(
+				"failed to find uncore domain with package {:?} and die {:?}",
+				self.package,
+				self.die
+			);
+		}
+
+		Ok(())
+	}
+}
+impl From<UncoreInfo> for UncoreConfig {
+	fn from(value: UncoreInfo) -> Self {
+		Self {
+			package: Some(value.package),
+			die: Some(value.die),
+			min_freq: FreqLimit::Absolute(value.min_freq),
+			max_freq: FreqLimit::Absolute(value.max_freq),
+		}
+	}
+}
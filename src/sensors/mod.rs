@@ -1,40 +1,82 @@
 use std::fmt::Display;
 
-use anyhow::Result;
-use cooling_profile::{CoolingProfileConfig, CoolingProfileInfo};
+use anyhow::{Context, Result};
+use backlight::{BrightnessConfig, BrightnessInfo};
+use battery::{AcAdapterInfo, BatteryConfig, BatteryInfo, battery_discharge};
+use cooling_device::{CoolingDeviceConfig, CoolingDeviceInfo, cooling_devices_write};
+use cooling_profile::{CoolingProfileConfig, CoolingProfileInfo, PlatformProfileConfig};
+use cpu_hotplug::{CpuHotplugConfig, CpuHotplugInfo};
+use cpuidle::{CpuidleConfig, CpuidleInfo};
+use fan::{FanConfig, FanInfo, fans_restore};
 use intel_gpu::{GpuConfig, GpuInfo};
 use intel_pstate::{PstateConfig, PstateInfo};
 use intel_rapl::{RaplZoneConfig, RaplZoneInfo};
+use intel_uncore::{UncoreConfig, UncoreInfo};
 use serde::{Deserialize, Serialize};
+use smt::{SmtConfig, SmtInfo};
 
 use crate::{
 	ppd::PpdProfile,
 	sensors::intel_dptf::{DptfConfig, DptfInfo},
 };
 
+pub mod backlight;
+pub mod battery;
+pub mod cooling_device;
 pub mod cooling_profile;
+pub mod cpu_hotplug;
+pub mod cpu_topology;
+pub mod cpuidle;
+pub mod dptf_gddv;
+pub mod fan;
+pub mod freq_limit;
+pub mod hwmon;
 pub mod intel_dptf;
 pub mod intel_gpu;
 pub mod intel_pstate;
 pub mod intel_rapl;
+pub mod intel_uncore;
+pub mod power_monitor;
+pub mod smt;
+pub mod thermal_zone;
 pub mod throttle;
 
 #[derive(Clone, Debug)]
 pub struct SensorInfo {
 	pub rapl: Vec<RaplZoneInfo>,
 	pub dptf: DptfInfo,
+	pub smt: SmtInfo,
+	pub hotplug: CpuHotplugInfo,
 	pub pstate: PstateInfo,
+	pub cpuidle: CpuidleInfo,
+	pub uncore: Vec<UncoreInfo>,
 	pub gpus: Vec<GpuInfo>,
 	pub cooling: CoolingProfileInfo,
+	pub cooling_devices: Vec<CoolingDeviceInfo>,
+	pub fans: FanInfo,
+	pub batteries: Vec<BatteryInfo>,
+	pub ac_adapters: Vec<AcAdapterInfo>,
+	pub backlights: Vec<BrightnessInfo>,
+	pub leds: Vec<BrightnessInfo>,
 }
 impl SensorInfo {
 	pub fn read() -> Result<Self> {
 		Ok(Self {
 			rapl: RaplZoneInfo::read_all()?,
 			dptf: DptfInfo::read()?,
+			smt: SmtInfo::read()?,
+			hotplug: CpuHotplugInfo::read()?,
 			pstate: PstateInfo::read()?,
+			cpuidle: CpuidleInfo::read()?,
+			uncore: UncoreInfo::read_all()?,
 			gpus: GpuInfo::read_all()?,
 			cooling: CoolingProfileInfo::read()?,
+			cooling_devices: CoolingDeviceInfo::read_all()?,
+			fans: FanInfo::read()?,
+			batteries: BatteryInfo::read_all()?,
+			ac_adapters: AcAdapterInfo::read_all()?,
+			backlights: BrightnessInfo::read_backlights()?,
+			leds: BrightnessInfo::read_leds()?,
 		})
 	}
 
@@ -45,14 +87,36 @@ impl SensorInfo {
 
 		self.dptf.write()?;
 
+		self.smt.write()?;
+
+		self.hotplug.write()?;
+
 		self.pstate.write()?;
 
+		self.cpuidle.write()?;
+
+		for domain in &self.uncore {
+			domain.write()?;
+		}
+
 		for gpu in &self.gpus {
 			gpu.write()?;
 		}
 
 		self.cooling.write()?;
 
+		cooling_devices_write(&self.cooling_devices)?;
+
+		self.fans.write()?;
+
+		for battery in &self.batteries {
+			battery.write()?;
+		}
+
+		for device in self.backlights.iter().chain(&self.leds) {
+			device.write()?;
+		}
+
 		Ok(())
 	}
 }
@@ -65,8 +129,19 @@ impl Display for SensorInfo {
 
 		writeln!(f, "{}", self.dptf)?;
 
+		writeln!(f, "{}", self.smt)?;
 		writeln!(f, "{}", self.pstate)?;
 
+		writeln!(f, "{}", self.cpuidle)?;
+
+		if !self.uncore.is_empty() {
+			writeln!(f, "Uncore:")?;
+			for domain in &self.uncore {
+				writeln!(f, "{domain}")?;
+			}
+			writeln!(f)?;
+		}
+
 		writeln!(f, "GPUs:")?;
 		for gpu in &self.gpus {
 			writeln!(f, "{gpu}")?;
@@ -74,6 +149,44 @@ impl Display for SensorInfo {
 
 		writeln!(f, "\n{}", self.cooling)?;
 
+		if !self.batteries.is_empty() || !self.ac_adapters.is_empty() {
+			writeln!(f, "\nPower supplies:")?;
+			for adapter in &self.ac_adapters {
+				writeln!(f, "{adapter}")?;
+			}
+			for battery in &self.batteries {
+				writeln!(f, "{battery}")?;
+			}
+			if let Some(power) = battery_discharge(&self.batteries) {
+				writeln!(f, "System discharge: {:.2}W", power as f64 / 1000000.0)?;
+			}
+		}
+
+		if !self.backlights.is_empty() {
+			writeln!(f, "\nBacklights:")?;
+			for backlight in &self.backlights {
+				writeln!(f, "{backlight}")?;
+			}
+		}
+
+		if !self.leds.is_empty() {
+			writeln!(f, "\nLEDs:")?;
+			for led in &self.leds {
+				writeln!(f, "{led}")?;
+			}
+		}
+
+		if !self.fans.pwm.is_empty() || self.fans.thinkpad.is_some() {
+			writeln!(f, "\n{}", self.fans)?;
+		}
+
+		if !self.cooling_devices.is_empty() {
+			writeln!(f, "\nCooling devices:")?;
+			for device in &self.cooling_devices {
+				writeln!(f, "{device}")?;
+			}
+		}
+
 		Ok(())
 	}
 }
@@ -82,26 +195,128 @@ impl Display for SensorInfo {
 pub struct SensorConfig {
 	pub rapl: Vec<RaplZoneConfig>,
 	pub dptf: DptfConfig,
+	#[serde(default)]
+	pub smt: Option<SmtConfig>,
+	#[serde(default)]
+	pub hotplug: Option<CpuHotplugConfig>,
 	pub pstate: PstateConfig,
+	#[serde(default)]
+	pub cpuidle: Option<CpuidleConfig>,
+	#[serde(default)]
+	pub uncore: Vec<UncoreConfig>,
 	pub gpus: Vec<GpuConfig>,
 	pub cooling: CoolingProfileConfig,
+	#[serde(default)]
+	pub platform_profiles: Vec<PlatformProfileConfig>,
+	#[serde(default)]
+	pub cooling_devices: Vec<CoolingDeviceConfig>,
+	#[serde(default)]
+	pub fans: Vec<FanConfig>,
+	#[serde(default)]
+	pub battery: Vec<BatteryConfig>,
+	#[serde(default)]
+	pub backlight: Vec<BrightnessConfig>,
+	#[serde(default)]
+	pub leds: Vec<BrightnessConfig>,
 	pub ppd_name: PpdProfile,
 }
 impl SensorConfig {
-	pub fn apply(&self, info: &mut SensorInfo) -> Result<()> {
+	/// `transition` is false when the daemon reapplies the profile it already applied, which skips
+	/// brightness entries marked `only_on_transition`
+	pub fn apply(&self, info: &mut SensorInfo, transition: bool) -> Result<()> {
 		for zone in &self.rapl {
 			zone.apply(&mut info.rapl)?;
 		}
 
 		self.dptf.apply(&mut info.dptf)?;
 
+		if let Some(smt) = &self.smt {
+			smt.apply(&mut info.smt)?;
+		}
+
+		if let Some(hotplug) = &self.hotplug {
+			hotplug.apply(&mut info.hotplug)?;
+		}
+
 		self.pstate.apply(&mut info.pstate)?;
 
+		if let Some(cpuidle) = &self.cpuidle {
+			cpuidle.apply(&mut info.cpuidle)?;
+		}
+
+		for domain in &self.uncore {
+			domain.apply(&mut info.uncore)?;
+		}
+
 		for gpu in &self.gpus {
 			gpu.apply(&mut info.gpus)?;
 		}
 
 		self.cooling.apply(&mut info.cooling)?;
+		for handler in &self.platform_profiles {
+			handler.apply(&mut info.cooling)?;
+		}
+
+		for device in &self.cooling_devices {
+			device.apply(&mut info.cooling_devices)?;
+		}
+
+		for fan in &self.fans {
+			fan.apply(&mut info.fans)?;
+		}
+
+		for battery in &self.battery {
+			battery.apply(&mut info.batteries)?;
+		}
+
+		for backlight in &self.backlight {
+			backlight.apply(&mut info.backlights, transition)?;
+		}
+
+		for led in &self.leds {
+			led.apply(&mut info.leds, transition)?;
+		}
+
+		Ok(())
+	}
+
+	/// Reads the current sensor data, applies this config to it and writes it back
+	///
+	/// Fans go back to the mode they were in before powerd took them over if that fails, rather
+	/// than staying at the previous profile's settings
+	pub fn write(&self, transition: bool) -> Result<()> {
+		let res = self.write_staged(transition);
+		if res.is_err() {
+			fans_restore();
+		}
+
+		res
+	}
+
+	fn write_staged(&self, transition: bool) -> Result<()> {
+		let mut info = SensorInfo::read().context("failed to read current sensor data")?;
+		self.apply(&mut info, transition)
+			.context("failed to apply config")?;
+
+		// smt and cpu hotplug bring cpus online or take them offline, and changing the intel_pstate
+		// status recreates every cpufreq policy, so everything per-cpu has to be reread before it
+		// can be written
+		type Stage = fn(&SensorInfo) -> Result<bool>;
+		let stages: [(&str, Stage); 3] = [
+			("smt", |x| x.smt.write()),
+			("cpu hotplug", |x| x.hotplug.write()),
+			("intel_pstate status", |x| x.pstate.write_status()),
+		];
+		for (name, write) in stages {
+			if write(&info).with_context(|| format!("failed to write {name} config"))? {
+				info = SensorInfo::read()
+					.with_context(|| format!("failed to reread sensor data after {name} change"))?;
+				self.apply(&mut info, transition)
+					.context("failed to apply config")?;
+			}
+		}
+
+		info.write().context("failed to write config")?;
 
 		Ok(())
 	}
@@ -111,9 +326,37 @@ impl From<SensorInfo> for SensorConfig {
 		Self {
 			rapl: value.rapl.into_iter().map(Into::into).collect(),
 			dptf: value.dptf.into(),
+			smt: value.smt.into(),
+			hotplug: Some(value.hotplug.into()),
 			pstate: value.pstate.into(),
+			cpuidle: Some(value.cpuidle.into()),
+			uncore: value.uncore.into_iter().map(Into::into).collect(),
 			gpus: value.gpus.into_iter().map(Into::into).collect(),
+			platform_profiles: value
+				.cooling
+				.handlers
+				.iter()
+				.cloned()
+				.map(Into::into)
+				.collect(),
 			cooling: value.cooling.into(),
+			// thermal governors usually drive cooling devices, so dumps leave them alone
+			cooling_devices: Vec::new(),
+			// fans stay under firmware control unless a profile asks for something else
+			fans: Vec::new(),
+			// brightness is the user's to change
+			backlight: Vec::new(),
+			leds: Vec::new(),
+			battery: value
+				.batteries
+				.into_iter()
+				.filter(|x| {
+					x.start_threshold.is_some()
+						|| x.end_threshold.is_some()
+						|| x.charge_behaviour.is_some()
+				})
+				.map(Into::into)
+				.collect(),
 			ppd_name: PpdProfile::Balanced,
 		}
 	}
@@ -0,0 +1,75 @@
+use std::{
+	fmt::Write as _,
+	io::Write,
+	thread::sleep,
+	time::{Duration, Instant},
+};
+
+use anyhow::Result;
+
+use crate::sensors::{
+	battery::{AcAdapterInfo, BatteryInfo, battery_discharge},
+	intel_rapl::RaplZoneInfo,
+};
+
+/// µJ between two readings of a counter that wraps around to 0 after `max_energy_range_uj`, `None`
+/// if it wrapped and the range is unknown
+fn energy_delta(prev: u64, energy: u64, max_range: Option<u64>) -> Option<u64> {
+	match energy.checked_sub(prev) {
+		Some(delta) => Some(delta),
+		None => Some(max_range?.checked_sub(prev)? + 1 + energy),
+	}
+}
+
+/// Prints the power of every top level RAPL zone next to the battery discharge every `interval`
+/// until writing fails, e.g. because the client went away
+pub fn power_monitor(out: &mut impl Write, interval: Duration) -> Result<()> {
+	let zones: Vec<RaplZoneInfo> = RaplZoneInfo::read_all()?
+		.into_iter()
+		.filter(|x| !x.mmio() && x.energy.is_some())
+		.collect();
+
+	let mut prev: Vec<Option<u64>> = zones.iter().map(|x| x.energy).collect();
+	let mut start = Instant::now();
+
+	loop {
+		sleep(interval);
+
+		let elapsed = start.elapsed().as_secs_f64();
+		start = Instant::now();
+
+		let mut line = String::new();
+		for (zone, prev) in zones.iter().zip(prev.iter_mut()) {
+			let energy = zone.read_energy()?;
+			if let (Some(energy), Some(prev)) = (energy, *prev)
+				&& let Some(delta) = energy_delta(prev, energy, zone.max_energy_range)
+			{
+				let joules = delta as f64 / 1000000.0;
+				write!(line, "{}: {:.2}W, ", zone.name, joules / elapsed)?;
+			}
+			*prev = energy;
+		}
+
+		match battery_discharge(&BatteryInfo::read_all()?) {
+			Some(power) => write!(line, "discharge: {:.2}W", power as f64 / 1000000.0)?,
+			None if AcAdapterInfo::read_all()?.iter().any(|x| x.online) => write!(line, "on AC")?,
+			None => write!(line, "discharge: unknown")?,
+		}
+
+		writeln!(out, "{line}")?;
+		out.flush()?;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::energy_delta;
+
+	#[test]
+	fn delta() {
+		assert_eq!(energy_delta(100, 250, None), Some(150));
+		assert_eq!(energy_delta(100, 100, Some(1000)), Some(0));
+		assert_eq!(energy_delta(990, 10, Some(999)), Some(20));
+		assert_eq!(energy_delta(990, 10, None), None);
+	}
+}
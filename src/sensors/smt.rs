@@ -0,0 +1,93 @@
+use std::{fmt::Display, path::Path};
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::sysfs::{sysfs_exists, sysfs_read, sysfs_write};
+
+const SMT_CONTROLS: &[&str] = &["on", "off", "forceoff"];
+
+#[derive(Clone, Debug)]
+pub struct SmtInfo {
+	current: Option<String>,
+	pub active: bool,
+
+	/// `None` if the kernel doesn't allow controlling SMT
+	pub control: Option<String>,
+}
+impl SmtInfo {
+	pub fn read() -> Result<Self> {
+		let root = Path::new("devices/system/cpu/smt");
+
+		if !sysfs_exists(root)? {
+			return Ok(Self {
+				current: None,
+				active: false,
+				control: None,
+			});
+		}
+
+		let control = Some(sysfs_read::<String>(&root.join("control"))?)
+			.filter(|x| SMT_CONTROLS.contains(&x.as_str()));
+
+		Ok(Self {
+			current: control.clone(),
+			active: sysfs_read::<usize>(&root.join("active"))? == 1,
+			control,
+		})
+	}
+
+	/// Returns whether SMT control changed, which brings sibling cpus online or takes them offline
+	pub fn write(&self) -> Result<bool> {
+		if let Some(control) = &self.control
+			&& self.current.as_ref() != Some(control)
+		{
+			sysfs_write(Path::new("devices/system/cpu/smt/control"), control)?;
+			Ok(true)
+		} else {
+			Ok(false)
+		}
+	}
+}
+impl Display for SmtInfo {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "SMT {}", if self.active { "active" } else { "inactive" })?;
+		if let Some(control) = &self.current {
+			write!(f, " (control \"{control}\")")?;
+		} else {
+			write!(f, " (not controllable)")?;
+		}
+
+		Ok(())
+	}
+}
+
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct SmtConfig(String);
+impl SmtConfig {
+	pub fn apply(&self, info: &mut SmtInfo) -> Result<()> {
+		if !SMT_CONTROLS.contains(&self.0.as_str()) {
+			bail!(
+				"invalid smt control {:?}, expected one of {SMT_CONTROLS:?}",
+				self.0
+			);
+		}
+
+		match info.current.as_deref() {
+			None => bail!("smt can't be controlled on this system"),
+			Some("forceoff") if self.0 != "forceoff" => {
+				bail!("smt is force disabled until the next reboot")
+			}
+			_ => {}
+		}
+
+		info.control = Some(self.0.clone());
+
+		Ok(())
+	}
+}
+impl From<SmtInfo> for Option<SmtConfig> {
+	fn from(value: SmtInfo) -> Self {
+		value.control.map(SmtConfig)
+	}
+}
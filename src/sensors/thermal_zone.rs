@@ -0,0 +1,194 @@
+use std::{
+	fmt::Display,
+	path::{Path, PathBuf},
+};
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::sysfs::{sysfs_exists, sysfs_list, sysfs_read, sysfs_writable, sysfs_write};
+
+const THERMAL_ROOT: &str = "class/thermal";
+
+#[derive(Clone, Debug)]
+pub struct TripPointInfo {
+	pub id: usize,
+	/// `passive`, `active`, `hot` or `critical`
+	pub trip_type: String,
+	pub writable: bool,
+	current: i64,
+	/// Millidegrees Celsius
+	pub temp: i64,
+}
+impl TripPointInfo {
+	fn read(zone: &Path, id: usize) -> Result<Self> {
+		let temp_path = zone.join(format!("trip_point_{id}_temp"));
+		let temp = sysfs_read(&temp_path)?;
+
+		Ok(Self {
+			id,
+			trip_type: sysfs_read(&zone.join(format!("trip_point_{id}_type")))?,
+			writable: sysfs_writable(&temp_path)?,
+			current: temp,
+			temp,
+		})
+	}
+
+	fn write(&self, zone: &Path) -> Result<()> {
+		if self.temp != self.current {
+			sysfs_write(
+				&zone.join(format!("trip_point_{}_temp", self.id)),
+				self.temp,
+			)?;
+		}
+
+		Ok(())
+	}
+}
+impl Display for TripPointInfo {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"trip {} {}: {}degC",
+			self.id,
+			self.trip_type,
+			self.temp as f64 / 1000.0
+		)?;
+		if self.writable {
+			write!(f, " (writable)")?;
+		}
+
+		Ok(())
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct ThermalZoneInfo {
+	path: PathBuf,
+	pub id: usize,
+	/// Stable name of the zone, e.g. `x86_pkg_temp` or `TCPU`
+	pub zone_type: String,
+	pub trips: Vec<TripPointInfo>,
+}
+impl ThermalZoneInfo {
+	fn read(path: PathBuf, id: usize) -> Result<Self> {
+		let mut trips = Vec::new();
+		while sysfs_exists(&path.join(format!("trip_point_{}_temp", trips.len())))? {
+			trips.push(TripPointInfo::read(&path, trips.len())?);
+		}
+
+		Ok(Self {
+			id,
+			zone_type: sysfs_read(&path.join("type"))?,
+			trips,
+			path,
+		})
+	}
+
+	pub fn read_all() -> Result<Vec<Self>> {
+		let root = Path::new(THERMAL_ROOT);
+		if !sysfs_exists(root)? {
+			return Ok(Vec::new());
+		}
+
+		let mut zones: Vec<(usize, String)> = sysfs_list(root)?
+			.into_iter()
+			.filter_map(|x| Some((x.strip_prefix("thermal_zone")?.parse().ok()?, x)))
+			.collect();
+		zones.sort_unstable();
+
+		zones
+			.into_iter()
+			.map(|(id, name)| Self::read(root.join(name), id))
+			.collect()
+	}
+
+	/// Millidegrees Celsius, `None` if the zone can't be read right now
+	pub fn read_temp(&self) -> Option<i64> {
+		// some zones return an error while their sensor is powered down
+		sysfs_read(&self.path.join("temp")).ok()
+	}
+
+	pub fn write(&self) -> Result<()> {
+		for trip in &self.trips {
+			trip.write(&self.path)?;
+		}
+
+		Ok(())
+	}
+}
+impl Display for ThermalZoneInfo {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "Thermal zone {} \"{}\"", self.id, self.zone_type)?;
+		for trip in &self.trips {
+			write!(f, "\n\t{trip}")?;
+		}
+
+		Ok(())
+	}
+}
+
+#[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct TripPointConfig {
+	/// `type` of the thermal zone, every zone with that type is changed
+	pub zone: String,
+	/// Every writable trip of the zone if unset
+	#[serde(default)]
+	pub trip: Option<usize>,
+	/// Only trips of this type, e.g. `passive`
+	#[serde(default)]
+	pub trip_type: Option<String>,
+	/// Millidegrees Celsius
+	pub temp: i64,
+}
+impl TripPointConfig {
+	pub fn apply(&self, zones: &mut [ThermalZoneInfo]) -> Result<()> {
+		let mut found = false;
+
+		for zone in zones.iter_mut().filter(|x| x.zone_type == self.zone) {
+			for trip in zone.trips.iter_mut().filter(|x| {
+				self.trip.is_none_or(|id| x.id == id)
+					&& self.trip_type.as_ref().is_none_or(|ty| *ty == x.trip_type)
+			}) {
+				if !trip.writable {
+					// an explicitly selected trip has to be writable, others are just skipped
+					if self.trip.is_some() {
+						bail!(
+							"trip {} of thermal zone {:?} is read-only",
+							trip.id,
+							self.zone
+						);
+					}
+					continue;
+				}
+				trip.temp = self.temp;
+				found = true;
+			}
+		}
+
+		if !found {
+			bail!(
+				"failed to find writable trip {:?} of type {:?} in thermal zone {:?}",
+				self.trip,
+				self.trip_type,
+				self.zone
+			);
+		}
+
+		Ok(())
+	}
+
+	/// Configs for every writable trip of a zone
+	pub fn from_zone(zone: &ThermalZoneInfo) -> Vec<Self> {
+		zone.trips
+			.iter()
+			.filter(|x| x.writable)
+			.map(|x| Self {
+				zone: zone.zone_type.clone(),
+				trip: Some(x.id),
+				trip_type: None,
+				temp: x.temp,
+			})
+			.collect()
+	}
+}
@@ -1,8 +1,19 @@
-use std::fmt::{Display, Write};
+use std::{
+	fmt::{Display, Write},
+	thread::sleep,
+	time::{Duration, Instant},
+};
 
 use anyhow::{Context, Result};
 
-use crate::msr::{Msr, msr_get_bit, msr_read};
+use crate::{
+	msr::{Msr, msr_get_bit, msr_read},
+	sensors::intel_gpu::GpuInfo,
+};
+
+const POWER_SAMPLE_TIME: Duration = Duration::from_secs(1);
+/// The perf limit reasons MSRs are package scoped, and cpu 0 is the one cpu that can't go offline
+const MSR_CPU: usize = 0;
 
 #[derive(Debug)]
 enum ThrottleReason {
@@ -58,8 +69,8 @@ fn print(ty: &str, reasons: Vec<ThrottleReason>) -> Result<String> {
 }
 
 pub fn cpu_throttling() -> Result<String> {
-	let msr =
-		msr_read(1, Msr::CpuPerfLimitReasons).context("failed to read cpu throttle reasons")?;
+	let msr = msr_read(MSR_CPU, Msr::CpuPerfLimitReasons)
+		.context("failed to read cpu throttle reasons")?;
 
 	let mut reasons = Vec::new();
 
@@ -86,8 +97,65 @@ pub fn cpu_throttling() -> Result<String> {
 	print("CPU", reasons)
 }
 
+/// Samples the energy counter of every card that has one
+fn gpu_power(gpus: &[GpuInfo]) -> Result<Vec<(&GpuInfo, f64)>> {
+	let mut cards: Vec<(&GpuInfo, u64)> = Vec::new();
+	for gpu in gpus {
+		if !cards.iter().any(|(x, _)| x.id == gpu.id)
+			&& let Some(energy) = gpu.read_energy()?
+		{
+			cards.push((gpu, energy));
+		}
+	}
+	if cards.is_empty() {
+		return Ok(Vec::new());
+	}
+
+	let start = Instant::now();
+	sleep(POWER_SAMPLE_TIME);
+
+	let mut power = Vec::new();
+	for (card, prev) in cards {
+		if let Some(energy) = card.read_energy()? {
+			let joules = energy.saturating_sub(prev) as f64 / 1000000.0;
+			power.push((card, joules / start.elapsed().as_secs_f64()));
+		}
+	}
+
+	Ok(power)
+}
+
+/// Prefers the reasons reported by the i915/xe driver, falls back to the MSR
 pub fn graphics_throttling() -> Result<String> {
-	let msr = msr_read(1, Msr::GraphicsPerfLimitReasons)
+	let gpus = GpuInfo::read_all()?;
+	let mut out = String::new();
+
+	for gpu in &gpus {
+		if let Some(reasons) = &gpu.throttle_reasons {
+			if !out.is_empty() {
+				writeln!(out)?;
+			}
+			write!(out, "GPU {} {} throttle reasons: ", gpu.pci_slot, gpu.gt)?;
+			if reasons.is_empty() {
+				write!(out, "None")?;
+			} else {
+				write!(out, "{}", reasons.join(", "))?;
+			}
+		}
+	}
+	if out.is_empty() {
+		out = msr_graphics_throttling()?;
+	}
+
+	for (gpu, power) in gpu_power(&gpus)? {
+		write!(out, "\nGPU {} power: {power:.2}W", gpu.pci_slot)?;
+	}
+
+	Ok(out)
+}
+
+fn msr_graphics_throttling() -> Result<String> {
+	let msr = msr_read(MSR_CPU, Msr::GraphicsPerfLimitReasons)
 		.context("failed to read graphics throttle reasons")?;
 
 	let mut reasons = Vec::new();
@@ -118,8 +186,8 @@ pub fn graphics_throttling() -> Result<String> {
 }
 
 pub fn ring_throttling() -> Result<String> {
-	let msr =
-		msr_read(1, Msr::RingPerfLimitReasons).context("failed to read ring throttle reasons")?;
+	let msr = msr_read(MSR_CPU, Msr::RingPerfLimitReasons)
+		.context("failed to read ring throttle reasons")?;
 
 	let mut reasons = Vec::new();
 
@@ -1,4 +1,4 @@
-use std::{error::Error, path::Path, str::FromStr};
+use std::{error::Error, os::unix::fs::PermissionsExt, path::Path, str::FromStr};
 
 use anyhow::{Context, Result, anyhow};
 
@@ -6,6 +6,18 @@ pub fn sysfs_exists(path: &Path) -> Result<bool> {
 	std::fs::exists(Path::new("/sys/").join(path)).context("failed to check if sysfs path exists")
 }
 
+/// Whether the owner may write a sysfs attribute, the kernel marks read-only attributes 0444
+pub fn sysfs_writable(path: &Path) -> Result<bool> {
+	std::fs::metadata(Path::new("/sys/").join(path))
+		.map(|x| x.permissions().mode() & 0o200 != 0)
+		.with_context(|| {
+			format!(
+				"failed to read sysfs permissions of {}",
+				path.to_str().unwrap_or_default()
+			)
+		})
+}
+
 pub fn sysfs_read<T>(path: &Path) -> Result<T>
 where
 	T: FromStr,
@@ -17,6 +29,55 @@ where
 		.with_context(|| format!("failed to read sysfs {}", path.to_str().unwrap_or_default()))
 }
 
+/// Reads a sysfs value, or returns `None` if the file doesn't exist
+pub fn sysfs_read_opt<T>(path: &Path) -> Result<Option<T>>
+where
+	T: FromStr,
+	<T as FromStr>::Err: Error + Sync + Send + 'static,
+{
+	if sysfs_exists(path)? {
+		sysfs_read(path).map(Some)
+	} else {
+		Ok(None)
+	}
+}
+
+/// Reads a binary sysfs attribute
+pub fn sysfs_read_bytes(path: &Path) -> Result<Vec<u8>> {
+	std::fs::read(Path::new("/sys/").join(path))
+		.with_context(|| format!("failed to read sysfs {}", path.to_str().unwrap_or_default()))
+}
+
+/// Reads the name of the file a sysfs symlink points to, e.g. the driver of a device
+pub fn sysfs_read_link_name(path: &Path) -> Result<String> {
+	std::fs::read_link(Path::new("/sys/").join(path))
+		.map_err(|x| anyhow!(x))
+		.and_then(|x| {
+			x.file_name()
+				.map(|x| x.to_string_lossy().into_owned())
+				.context("symlink has no file name")
+		})
+		.with_context(|| {
+			format!(
+				"failed to read sysfs link {}",
+				path.to_str().unwrap_or_default()
+			)
+		})
+}
+
+/// Lists the entries of a sysfs directory, sorted by name
+pub fn sysfs_list(path: &Path) -> Result<Vec<String>> {
+	let mut entries = std::fs::read_dir(Path::new("/sys/").join(path))
+		.and_then(|x| {
+			x.map(|x| x.map(|x| x.file_name().to_string_lossy().into_owned()))
+				.collect::<Result<Vec<_>, _>>()
+		})
+		.with_context(|| format!("failed to list sysfs {}", path.to_str().unwrap_or_default()))?;
+	entries.sort();
+
+	Ok(entries)
+}
+
 pub fn sysfs_write(path: &Path, val: impl ToString) -> Result<()> {
 	let string = val.to_string();
 	std::fs::write(Path::new("/sys/").join(path), string.as_bytes()).with_context(|| {
@@ -26,3 +87,63 @@ pub fn sysfs_write(path: &Path, val: impl ToString) -> Result<()> {
 		)
 	})
 }
+
+/// Parses a kernel cpulist such as `0-3,8,10-11`
+pub fn parse_cpulist(list: &str) -> Result<Vec<usize>> {
+	let mut cpus = Vec::new();
+
+	for part in list.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+		if let Some((start, end)) = part.split_once('-') {
+			let start: usize = start
+				.parse()
+				.with_context(|| format!("invalid cpu {start:?}"))?;
+			let end: usize = end
+				.parse()
+				.with_context(|| format!("invalid cpu {end:?}"))?;
+			cpus.extend(start..=end);
+		} else {
+			cpus.push(
+				part.parse()
+					.with_context(|| format!("invalid cpu {part:?}"))?,
+			);
+		}
+	}
+
+	Ok(cpus)
+}
+
+/// Formats cpus as a kernel cpulist, collapsing consecutive ids into ranges
+pub fn format_cpulist(cpus: &[usize]) -> String {
+	let mut cpus = cpus.to_vec();
+	cpus.sort_unstable();
+	cpus.dedup();
+
+	let mut ranges: Vec<(usize, usize)> = Vec::new();
+	for cpu in cpus {
+		match ranges.last_mut() {
+			Some((_, end)) if *end + 1 == cpu => *end = cpu,
+			_ => ranges.push((cpu, cpu)),
+		}
+	}
+
+	ranges
+		.into_iter()
+		.map(|(start, end)| {
+			if start == end {
+				start.to_string()
+			} else {
+				format!("{start}-{end}")
+			}
+		})
+		.collect::<Vec<_>>()
+		.join(",")
+}
+
+pub fn sysfs_read_cpulist(path: &Path) -> Result<Vec<usize>> {
+	parse_cpulist(&sysfs_read::<String>(path)?).with_context(|| {
+		format!(
+			"failed to parse sysfs cpulist {}",
+			path.to_str().unwrap_or_default()
+		)
+	})
+}